@@ -1,29 +1,89 @@
+mod abandon;
+mod achievement;
+mod cursor;
+mod daily;
+mod drag;
 mod foundation;
+mod gamepad;
+mod golf;
+mod history;
+mod layout;
+mod leaderboard;
+mod menu;
+mod notation;
+mod peaks;
 mod piles;
+mod replay;
+mod reserve;
+mod rules;
+mod score;
+mod settings;
+mod speedrun;
+mod sound;
+mod stats;
 mod systems;
 mod stock;
+mod table;
+mod touch;
+mod variant;
 mod waste;
+mod win;
 
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
-use piles::Pile;
 use strum::IntoEnumIterator;
 use strum_macros::EnumIter;
 use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
 
-use self::piles::{spawn_pile, format_piles};
+use self::abandon::{Abandon, AbandonPlugin};
+use self::achievement::AchievementPlugin;
+use self::piles::spawn_pile;
+use self::cursor::CursorPlugin;
+use self::daily::DailyPlugin;
+use self::drag::DragPlugin;
 use self::foundation::spawn_foundations;
+use self::golf::Streak;
+use self::history::{History, HistoryPlugin};
+use self::layout::LayoutPlugin;
+use self::leaderboard::LeaderboardPlugin;
+use self::menu::MenuPlugin;
+use self::notation::{Loaded, NotationPlugin};
+use self::peaks::spawn_peaks;
+use self::replay::ReplayPlugin;
+use self::reserve::spawn_reserve;
+use self::score::ScorePlugin;
+use self::settings::{Settings, SettingsPlugin};
+use self::sound::SoundPlugin;
+use self::speedrun::SpeedRunPlugin;
+use self::stats::StatsPlugin;
 use self::stock::spawn_stock;
-use self::systems::SystemsPlugin;
+use self::systems::{GameEvent, SystemsPlugin};
+use self::table::spawn_table;
+use self::variant::{HouseRules, Variant};
 use self::waste::spawn_waste;
+use self::win::Won;
 
 pub struct CardPlugin;
 
 impl Plugin for CardPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, (spawn_camera, spawn_board))
-            .add_plugins(SystemsPlugin)
-            .add_systems(Update, keyboard_input);
+        app.init_resource::<Variant>()
+            .init_resource::<HouseRules>()
+            .init_resource::<Streak>()
+            .init_resource::<Won>()
+            .init_resource::<Seed>()
+            .init_state::<GameState>()
+            .add_event::<NewGame>()
+            .add_systems(Startup, spawn_camera)
+            .configure_sets(Update, (
+                Dealing.before(Gameplay),
+                Gameplay.run_if(in_state(GameState::Playing).or_else(in_state(GameState::Replay))),
+                Input.in_set(Gameplay).run_if(in_state(GameState::Playing)),
+            ))
+            .add_plugins((SystemsPlugin, CursorPlugin, DragPlugin, HistoryPlugin, LayoutPlugin, SettingsPlugin, SoundPlugin))
+            .add_plugins((AbandonPlugin, AchievementPlugin, DailyPlugin, LeaderboardPlugin, MenuPlugin, NotationPlugin, ReplayPlugin, ScorePlugin, SpeedRunPlugin, StatsPlugin))
+            .add_systems(Update, (keyboard_input.in_set(Input), new_game.in_set(Dealing)));
     }
 }
 
@@ -35,13 +95,61 @@ enum CardSuit {
     Spades
 }
 
+impl CardSuit {
+    fn is_red(self) -> bool {
+        matches!(self, CardSuit::Hearts | CardSuit::Diamonds)
+    }
+}
+
 #[derive(Component, Copy, Clone, Debug, PartialEq)]
 struct CardFace(u8);
 
+#[derive(Copy, Clone, Debug, PartialEq, strum_macros::Display)]
+enum JokerColour {
+    Red,
+    Black
+}
+
 #[derive(Component, Copy, Clone, Debug, PartialEq)]
-struct Card {
-    suit: CardSuit,
-    face: CardFace,
+enum Card {
+    Suited {
+        suit: CardSuit,
+        face: CardFace,
+    },
+    /// A wildcard for the tableau, resolved to the card it stands in for once one goes on it.
+    Joker {
+        colour: JokerColour,
+        stands_for: Option<(CardSuit, CardFace)>,
+    },
+}
+
+impl Card {
+    /// The suit the card counts as. Jokers have none until they are resolved.
+    fn suit(&self) -> Option<CardSuit> {
+        match *self {
+            Card::Suited { suit, .. } => Some(suit),
+            Card::Joker { stands_for, .. } => stands_for.map(|(suit, _)| suit),
+        }
+    }
+
+    /// The rank the card counts as. Jokers have none until they are resolved.
+    fn face(&self) -> Option<CardFace> {
+        match *self {
+            Card::Suited { face, .. } => Some(face),
+            Card::Joker { stands_for, .. } => stands_for.map(|(_, face)| face),
+        }
+    }
+
+    fn is_joker(&self) -> bool {
+        matches!(self, Card::Joker { .. })
+    }
+
+    fn texture(&self) -> String {
+        match self {
+            Card::Suited { suit, face } => format!("cards/{} {}.png", suit, face.0),
+            Card::Joker { colour, .. } => format!("cards/Joker {}.png", colour),
+        }
+    }
 }
 
 #[derive(Bundle)]
@@ -57,6 +165,59 @@ struct Camera;
 #[derive(Component)]
 struct Board;
 
+/// Clears the table and deals the cards out again.
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+enum NewGame {
+    /// The current `Variant`, shuffled by the current `Seed`.
+    Deal,
+    /// The same deal again, from the position it was dealt in.
+    Restart,
+    /// The `Loaded` position.
+    Load,
+}
+
+/// Which screen the game is on. Cards can only be moved while `Playing`.
+#[derive(States, Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum GameState {
+    /// The title screen, where the variant and house rules are chosen.
+    #[default]
+    MainMenu,
+    Playing,
+    /// The cards are hidden until the game is resumed.
+    Paused,
+    /// The deal has been won, and the cards stay on the table behind the win screen.
+    Won,
+    Settings,
+    Stats,
+    /// Asking the player whether to give up the deal in progress.
+    Confirm,
+    /// Watching a recorded game being played back.
+    Replay,
+    /// The calendar of daily deals.
+    Daily,
+    /// The high-score tables.
+    Leaderboards,
+    Achievements,
+    /// A countdown ran out before the deal was won.
+    TimeUp,
+}
+
+/// What the current deal was shuffled with, so that it can be dealt again the same way.
+#[derive(Resource, Default, Copy, Clone, Debug, PartialEq)]
+struct Seed(u64);
+
+/// Systems that take the cards off the table and deal them out again, before `Gameplay`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct Dealing;
+
+/// The systems that move cards around. They run while `Playing` and during replays.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct Gameplay;
+
+/// The part of `Gameplay` that turns the player's input into moves, only while `Playing`.
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+struct Input;
+
 fn spawn_camera(mut commands: Commands) {
     commands.spawn((
         Camera2dBundle::default(),
@@ -64,35 +225,111 @@ fn spawn_camera(mut commands: Commands) {
     ));
 }
 
-fn spawn_board(mut commands: Commands, asset_server: Res<AssetServer>) {
+fn spawn_board(mut commands: Commands, asset_server: Res<AssetServer>, variant: Res<Variant>, house_rules: Res<HouseRules>, seed: Res<Seed>) {
 
     let mut stock = vec![];
-    for suit in CardSuit::iter() {
-        for face in 1..14 {
-            let card = Card { suit, face: CardFace(face) };
-            stock.push(card)
+    for _ in 0..variant.decks() {
+        for suit in CardSuit::iter() {
+            for face in 1..14 {
+                let card = Card::Suited { suit, face: CardFace(face) };
+                stock.push(card)
+            }
         }
     }
 
-    let mut rng = rand::thread_rng();
+    if house_rules.jokers && variant.allows_jokers() {
+        stock.push(Card::Joker { colour: JokerColour::Red, stands_for: None });
+        stock.push(Card::Joker { colour: JokerColour::Black, stands_for: None });
+    }
+
+    // `StdRng` can change between versions of rand, which would change every seed's deal.
+    let mut rng = ChaCha8Rng::seed_from_u64(seed.0);
     stock.shuffle(&mut rng);
-    
-    spawn_pile(&mut commands, stock.split_off(stock.len() - 1), 1, &asset_server);
-    spawn_pile(&mut commands, stock.split_off(stock.len() - 2), 2, &asset_server);
-    spawn_pile(&mut commands, stock.split_off(stock.len() - 3), 3, &asset_server);
-    spawn_pile(&mut commands, stock.split_off(stock.len() - 4), 4, &asset_server);
-    spawn_pile(&mut commands, stock.split_off(stock.len() - 5), 5, &asset_server);
-    spawn_pile(&mut commands, stock.split_off(stock.len() - 6), 6, &asset_server);
-    spawn_pile(&mut commands, stock.split_off(stock.len() - 7), 7, &asset_server);
 
-    spawn_stock(&mut commands, stock, &asset_server);
+    let foundation_column = variant.foundation_column();
 
-    spawn_foundations(&mut commands, &asset_server);
+    match *variant {
+        Variant::Klondike => {
+            for x in 0..7 {
+                spawn_pile(&mut commands, stock.split_off(stock.len() - (x + 1)), x, variant.pile_column(x), &asset_server);
+            }
 
-    spawn_waste(&mut commands, &asset_server);
-}
+            spawn_foundations(&mut commands, None, 1, foundation_column, &asset_server);
 
+            spawn_waste(&mut commands, None, &asset_server);
+        }
+        Variant::Yukon | Variant::Russian => {
+            spawn_pile(&mut commands, stock.split_off(stock.len() - 1), 0, variant.pile_column(0), &asset_server);
+            for x in 1..7 {
+                spawn_pile(&mut commands, stock.split_off(stock.len() - (x + 5)), x, variant.pile_column(x), &asset_server);
+            }
+
+            spawn_foundations(&mut commands, None, 1, foundation_column, &asset_server);
+        }
+        Variant::FortyThieves => {
+            for x in 0..10 {
+                spawn_pile(&mut commands, stock.split_off(stock.len() - 4), 0, variant.pile_column(x), &asset_server);
+            }
 
+            spawn_foundations(&mut commands, None, 2, foundation_column, &asset_server);
+
+            spawn_waste(&mut commands, None, &asset_server);
+        }
+        Variant::DoubleKlondike => {
+            for x in 0..9 {
+                spawn_pile(&mut commands, stock.split_off(stock.len() - (x + 1)), x, variant.pile_column(x), &asset_server);
+            }
+
+            spawn_foundations(&mut commands, None, 2, foundation_column, &asset_server);
+
+            spawn_waste(&mut commands, None, &asset_server);
+        }
+        Variant::Scorpion => {
+            for x in 0..7 {
+                let face_down = if x < 4 { 3 } else { 0 };
+                spawn_pile(&mut commands, stock.split_off(stock.len() - 7), face_down, variant.pile_column(x), &asset_server);
+            }
+        }
+        Variant::BakersDozen => {
+            for x in 0..13 {
+                let mut cards = stock.split_off(stock.len() - 4);
+                // Kings go to the bottom of their pile, where they don't block anything.
+                cards.sort_by_key(|card| card.face() != Some(CardFace(13)));
+                spawn_pile(&mut commands, cards, 0, variant.pile_column(x), &asset_server);
+            }
+
+            spawn_foundations(&mut commands, None, 1, foundation_column, &asset_server);
+        }
+        Variant::Canfield => {
+            spawn_reserve(&mut commands, stock.split_off(stock.len() - 13), &asset_server);
+            for x in 0..4 {
+                spawn_pile(&mut commands, stock.split_off(stock.len() - 1), 0, variant.pile_column(x), &asset_server);
+            }
+
+            // The foundations' base rank is taken from the first card that isn't a joker.
+            let first = stock.iter().rposition(|card| !card.is_joker()).map(|i| stock.remove(i));
+            spawn_foundations(&mut commands, first, 1, foundation_column, &asset_server);
+
+            spawn_waste(&mut commands, None, &asset_server);
+        }
+        Variant::Golf => {
+            for x in 0..7 {
+                spawn_pile(&mut commands, stock.split_off(stock.len() - 5), 0, variant.pile_column(x), &asset_server);
+            }
+
+            spawn_waste(&mut commands, stock.pop(), &asset_server);
+        }
+        Variant::TriPeaks => {
+            spawn_peaks(&mut commands, stock.split_off(stock.len() - 28), &asset_server);
+
+            spawn_waste(&mut commands, stock.pop(), &asset_server);
+        }
+    }
+
+    if !stock.is_empty() {
+        spawn_stock(&mut commands, stock, &asset_server);
+    }
+}
 
 fn game_reset(
     commands: &mut Commands, 
@@ -103,17 +340,56 @@ fn game_reset(
     }
 }
 
+/// F5 deals a new game and Shift+F5 restarts this one, F6, F7 and F9 change the variant,
+/// jokers and drawing three for the next deal, and F8 changes the layout.
 fn keyboard_input(
     keys: Res<ButtonInput<KeyCode>>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    mut settings: ResMut<Settings>,
+    mut abandon_event: EventWriter<Abandon>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::F5) {
+        if shift {
+            abandon_event.send(Abandon::Restart);
+        } else {
+            abandon_event.send(Abandon::NewDeal { variant: *variant, house_rules: *house_rules });
+        }
+    }
+    if keys.just_pressed(KeyCode::F6) {
+        abandon_event.send(Abandon::NewDeal { variant: variant.next(), house_rules: *house_rules });
+    }
+    if keys.just_pressed(KeyCode::F7) {
+        abandon_event.send(Abandon::NewDeal { variant: *variant, house_rules: HouseRules { jokers: !house_rules.jokers, ..*house_rules } });
+    }
+    if keys.just_pressed(KeyCode::F9) {
+        abandon_event.send(Abandon::NewDeal { variant: *variant, house_rules: HouseRules { draw_three: !house_rules.draw_three, ..*house_rules } });
+    }
+    if keys.just_pressed(KeyCode::F8) {
+        settings.handedness = settings.handedness.next();
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn new_game(
     mut commands: Commands,
+    mut new_game_event: EventReader<NewGame>,
     cards: Query<Entity, With<Board>>,
     asset_server: Res<AssetServer>,
-    q_pile: Query<&Children, With<Pile>>,
-    q_children: Query<&Children, With<Card>>,
-    mut transform_query: Query<&mut Transform, With<Card>>) {
-    if keys.just_pressed(KeyCode::F5) {
-        game_reset(&mut commands, cards);
-        spawn_board(commands, asset_server);
-        format_piles(&q_pile, &q_children, &mut transform_query)
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    seed: Res<Seed>,
+    history: Res<History>,
+    loaded: Res<Loaded>,
+    mut game_event: EventWriter<GameEvent>) {
+    let Some(&new_game) = new_game_event.read().last() else {
+        return;
+    };
+    game_event.send(GameEvent::Dealt);
+    game_reset(&mut commands, cards);
+    match (new_game, history.start()) {
+        (NewGame::Restart, Some(start)) => spawn_table(&mut commands, start, &asset_server),
+        (NewGame::Load, _) => spawn_table(&mut commands, &loaded.table, &asset_server),
+        _ => spawn_board(commands, asset_server, variant, house_rules, seed),
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,162 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+use super::daily::{DailyGame, Date};
+use super::history::History;
+use super::variant::{HouseRules, Variant};
+use super::win::Won;
+use super::{Board, Dealing, GameState, NewGame, Seed};
+
+pub struct AbandonPlugin;
+
+impl Plugin for AbandonPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Pending>()
+            .add_event::<Abandon>()
+            .add_event::<Confirm>()
+            .add_systems(Update, (
+                confirm_keys.run_if(in_state(GameState::Confirm)),
+                ask_to_abandon,
+                answer,
+            ).chain().before(Dealing));
+    }
+}
+
+/// Gives up on the deal on the table. Once any moves have been made the player is asked first.
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub enum Abandon {
+    /// Shuffle and deal a fresh game, which may be of a different variant.
+    NewDeal { variant: Variant, house_rules: HouseRules },
+    /// Put the cards back as they were dealt.
+    Restart,
+    /// Watch the last game played.
+    Replay,
+    /// Set out the `Loaded` position, which is a game of `variant`.
+    Load { variant: Variant },
+    /// Deal today's daily deal.
+    Daily,
+}
+
+impl Abandon {
+    pub fn question(self) -> &'static str {
+        match self {
+            Abandon::NewDeal { .. } => "New game? Current game will count as a loss",
+            Abandon::Restart => "Restart this deal? Your moves will be lost",
+            Abandon::Replay => "Watch the last game? Current game will count as a loss",
+            Abandon::Load { .. } => "Load the position? Current game will count as a loss",
+            Abandon::Daily => "Play the daily deal? Current game will count as a loss",
+        }
+    }
+}
+
+/// The player's answer to the question for the `Pending` abandon.
+#[derive(Event)]
+pub struct Confirm(pub bool);
+
+/// An abandon waiting on the player, and the screen to go back to if they change their mind.
+#[derive(Resource, Default)]
+pub struct Pending(pub Option<(Abandon, GameState)>);
+
+/// Enter or Y (South on a gamepad) says yes, Escape or N (East) says no.
+fn confirm_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    mut confirm_event: EventWriter<Confirm>,
+) {
+    let pressed = |button| gamepads.iter().any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, button)));
+    if keys.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter, KeyCode::KeyY]) || pressed(GamepadButtonType::South) {
+        confirm_event.send(Confirm(true));
+    } else if keys.any_just_pressed([KeyCode::Escape, KeyCode::KeyN]) || pressed(GamepadButtonType::East) {
+        confirm_event.send(Confirm(false));
+    }
+}
+
+/// Everything needed to leave the current deal.
+#[derive(SystemParam)]
+struct Leave<'w> {
+    variant: ResMut<'w, Variant>,
+    house_rules: ResMut<'w, HouseRules>,
+    seed: ResMut<'w, Seed>,
+    daily_game: ResMut<'w, DailyGame>,
+    new_game_event: EventWriter<'w, NewGame>,
+    next_state: ResMut<'w, NextState<GameState>>,
+}
+
+impl Leave<'_> {
+    fn leave(&mut self, abandon: Abandon) {
+        if abandon != Abandon::Restart {
+            self.daily_game.date = None;
+        }
+        match abandon {
+            Abandon::NewDeal { variant, house_rules } => {
+                *self.variant = variant;
+                *self.house_rules = house_rules;
+                self.seed.0 = random();
+                self.new_game_event.send(NewGame::Deal);
+                self.next_state.set(GameState::Playing);
+            }
+            Abandon::Restart => {
+                self.new_game_event.send(NewGame::Restart);
+                self.next_state.set(GameState::Playing);
+            }
+            // The replay deals its own game once it starts.
+            Abandon::Replay => self.next_state.set(GameState::Replay),
+            Abandon::Load { variant } => {
+                *self.variant = variant;
+                self.new_game_event.send(NewGame::Load);
+                self.next_state.set(GameState::Playing);
+            }
+            // Everyone plays Klondike without house rules, so that the deal is the same for all.
+            Abandon::Daily => {
+                let today = Date::today();
+                *self.variant = Variant::Klondike;
+                *self.house_rules = HouseRules::default();
+                self.seed.0 = today.seed();
+                self.daily_game.date = Some(today);
+                self.new_game_event.send(NewGame::Deal);
+                self.next_state.set(GameState::Playing);
+            }
+        }
+    }
+}
+
+fn ask_to_abandon(
+    mut abandon_event: EventReader<Abandon>,
+    history: Res<History>,
+    won: Res<Won>,
+    state: Res<State<GameState>>,
+    q_board: Query<(), With<Board>>,
+    mut pending: ResMut<Pending>,
+    mut leave: Leave,
+) {
+    let Some(&abandon) = abandon_event.read().last() else {
+        return;
+    };
+    // A deal that ran out of time is already lost.
+    if history.has_moves() && !won.0 && !q_board.is_empty() && *state.get() != GameState::TimeUp {
+        pending.0 = Some((abandon, *state.get()));
+        leave.next_state.set(GameState::Confirm);
+    } else {
+        leave.leave(abandon);
+    }
+}
+
+fn answer(
+    mut confirm_event: EventReader<Confirm>,
+    mut pending: ResMut<Pending>,
+    mut leave: Leave,
+) {
+    let Some(&Confirm(yes)) = confirm_event.read().last() else {
+        return;
+    };
+    let Some((abandon, back_to)) = pending.0.take() else {
+        return;
+    };
+    if yes {
+        leave.leave(abandon);
+    } else {
+        leave.next_state.set(back_to);
+    }
+}
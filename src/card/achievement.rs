@@ -0,0 +1,241 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::daily::Date;
+use super::leaderboard::Mode;
+use super::score::{count_score, Score};
+use super::settings::{load, save};
+use super::systems::GameEvent;
+use super::variant::{HouseRules, Variant};
+use super::win::GameWon;
+use super::{GameState, NewGame};
+
+pub struct AchievementPlugin;
+
+impl Plugin for AchievementPlugin {
+    fn build(&self, app: &mut App) {
+        let achievements = ron::from_str(include_str!("../../assets/achievements.ron"))
+            .expect("assets/achievements.ron should be a list of achievements");
+        app.insert_resource(Achievements(achievements))
+            .insert_resource(load::<Progress>("achievements.ron"))
+            .init_resource::<Attempt>()
+            .add_event::<Unlocked>()
+            .add_systems(Update, (
+                track_achievements.after(count_score),
+                save_progress,
+                notify,
+                fade_toasts,
+            ).chain());
+    }
+}
+
+/// Something to aim for, read from `assets/achievements.ron`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Achievement {
+    pub name: String,
+    pub description: String,
+    pub goal: Goal,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum Goal {
+    /// Win a deal while meeting every condition.
+    Win(Vec<Condition>),
+    /// Get a counter up to the number.
+    Reach(Counter, u32),
+}
+
+#[derive(Deserialize, Copy, Clone, Debug)]
+pub enum Condition {
+    NoUndo,
+    /// Without going through the stock again.
+    NoRecycle,
+    /// In fewer seconds of play.
+    Under(f32),
+    Jokers,
+    DrawThree,
+    Variant(Variant),
+}
+
+impl Condition {
+    fn met(self, attempt: &Attempt, score: &Score, mode: Mode) -> bool {
+        match self {
+            Condition::NoUndo => !attempt.undone,
+            Condition::NoRecycle => !attempt.recycled,
+            Condition::Under(seconds) => score.seconds < seconds,
+            Condition::Jokers => mode.jokers,
+            Condition::DrawThree => mode.draw_three,
+            Condition::Variant(variant) => mode.variant == variant,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Counter {
+    Wins,
+    /// Wins since the last deal given up on.
+    WinStreak,
+    /// The most cards moved at once that went down a rank at a time.
+    LongestRun,
+}
+
+/// Every achievement there is, in the order they're listed.
+#[derive(Resource)]
+pub struct Achievements(pub Vec<Achievement>);
+
+/// The achievements unlocked so far and the counters working towards the rest, kept between runs.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Progress {
+    /// When each achievement was unlocked, by name.
+    pub unlocked: BTreeMap<String, Date>,
+    pub counters: HashMap<Counter, u32>,
+}
+
+impl Progress {
+    pub fn count(&self, counter: Counter) -> u32 {
+        self.counters.get(&counter).copied().unwrap_or_default()
+    }
+}
+
+/// What has happened so far in the current deal.
+#[derive(Resource, Default)]
+struct Attempt {
+    /// A move has been made, so dealing again gives up on it.
+    started: bool,
+    won: bool,
+    undone: bool,
+    recycled: bool,
+}
+
+/// An achievement was just unlocked, by its place in `Achievements`.
+#[derive(Event)]
+pub struct Unlocked(pub usize);
+
+/// A notice of an unlocked achievement, shown until its timer runs out.
+#[derive(Component)]
+struct Toast(Timer);
+
+/// Only deals dealt from a seed count. Replays and loaded positions don't.
+#[allow(clippy::too_many_arguments)]
+fn track_achievements(
+    mut new_game_event: EventReader<NewGame>,
+    mut game_event: EventReader<GameEvent>,
+    mut game_won_event: EventReader<GameWon>,
+    state: Res<State<GameState>>,
+    score: Res<Score>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    achievements: Res<Achievements>,
+    mut attempt: ResMut<Attempt>,
+    mut progress: ResMut<Progress>,
+    mut unlocked_event: EventWriter<Unlocked>,
+) {
+    if *state.get() == GameState::Replay {
+        new_game_event.clear();
+        game_event.clear();
+        game_won_event.clear();
+        return;
+    }
+    if let Some(&new_game) = new_game_event.read().last() {
+        // Starting the same deal again is another go at it rather than giving up.
+        if new_game != NewGame::Restart && attempt.started && !attempt.won {
+            progress.counters.insert(Counter::WinStreak, 0);
+        }
+        *attempt = Attempt::default();
+        game_event.clear();
+    }
+    if !score.ranked {
+        game_event.clear();
+        game_won_event.clear();
+        return;
+    }
+
+    for &event in game_event.read() {
+        match event {
+            GameEvent::Moved { run, .. } => {
+                attempt.started = true;
+                if run as u32 > progress.count(Counter::LongestRun) {
+                    progress.counters.insert(Counter::LongestRun, run as u32);
+                }
+            }
+            GameEvent::Drew | GameEvent::Dealt => attempt.started = true,
+            GameEvent::Recycled => {
+                attempt.started = true;
+                attempt.recycled = true;
+            }
+            GameEvent::Undone => attempt.undone = true,
+            GameEvent::PickedUp | GameEvent::Rejected | GameEvent::Flipped | GameEvent::Redone => {}
+        }
+    }
+    let won = game_won_event.read().last().is_some() && !attempt.won;
+    if won {
+        attempt.won = true;
+        *progress.counters.entry(Counter::Wins).or_default() += 1;
+        *progress.counters.entry(Counter::WinStreak).or_default() += 1;
+    }
+
+    let mode = Mode::new(*variant, *house_rules);
+    for (index, achievement) in achievements.0.iter().enumerate() {
+        if progress.unlocked.contains_key(&achievement.name) {
+            continue;
+        }
+        let unlocked = match &achievement.goal {
+            Goal::Win(conditions) => won && conditions.iter().all(|condition| condition.met(&attempt, &score, mode)),
+            &Goal::Reach(counter, target) => progress.count(counter) >= target,
+        };
+        if unlocked {
+            progress.unlocked.insert(achievement.name.clone(), Date::today());
+            unlocked_event.send(Unlocked(index));
+        }
+    }
+}
+
+fn save_progress(progress: Res<Progress>) {
+    if progress.is_changed() && !progress.is_added() {
+        save("achievements.ron", &*progress);
+    }
+}
+
+/// Shows each achievement as it's unlocked in the top corner for a few seconds.
+fn notify(
+    mut commands: Commands,
+    mut unlocked_event: EventReader<Unlocked>,
+    achievements: Res<Achievements>,
+    q_toasts: Query<(), With<Toast>>,
+) {
+    for (showing, &Unlocked(index)) in (q_toasts.iter().count()..).zip(unlocked_event.read()) {
+        let achievement = &achievements.0[index];
+        commands.spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(16.0 + 80.0 * showing as f32),
+                    right: Val::Px(16.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(12.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.05, 0.2, 0.1, 0.95).into(),
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            Toast(Timer::new(Duration::from_secs(4), TimerMode::Once)),
+        )).with_children(|toast| {
+            let line = |text: String, size| TextBundle::from_section(text, TextStyle { font_size: size, color: Color::WHITE, ..default() });
+            toast.spawn(line(format!("Achievement unlocked: {}", achievement.name), 24.0));
+            toast.spawn(line(achievement.description.clone(), 18.0));
+        });
+    }
+}
+
+fn fade_toasts(mut commands: Commands, time: Res<Time>, mut q_toasts: Query<(Entity, &mut Toast)>) {
+    for (entity, mut toast) in q_toasts.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+
+use super::foundation::Foundation;
+use super::gamepad::gamepad_cursor;
+use super::history::{Redo, Undo};
+use super::layout::{Layout, Place};
+use super::peaks::Peaks;
+use super::piles::Base;
+use super::reserve::Reserve;
+use super::rules::Rules;
+use super::stock::Stock;
+use super::systems::{Activate, GameEvent, MoveRequest};
+use super::waste::Waste;
+use super::{Input, NewGame};
+
+pub struct CursorPlugin;
+
+impl Plugin for CursorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Cursor>()
+            .add_event::<CursorAction>()
+            .add_systems(Startup, spawn_cursor)
+            .add_systems(Update, (keyboard_cursor, gamepad_cursor, move_cursor, draw_cursor).chain().in_set(Input))
+            .add_systems(Update, reset_cursor);
+    }
+}
+
+/// Something to do with the selection cursor, whichever device it came from.
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub enum CursorAction {
+    Next,
+    Previous,
+    /// Select a card further down the pile, to pick up a longer run.
+    Up,
+    /// Select a card further up the pile, towards its top card.
+    Down,
+    /// Pick up the selected card, put down the held card, or draw from the stock.
+    Select,
+    /// Put the held card back where it came from.
+    Cancel,
+    /// Draw from the stock wherever the cursor is.
+    Draw,
+    /// Move the cursor to a card that can be played, or to the stock if there is none.
+    Hint,
+}
+
+/// The card or pile selected for keyboard play, and the card picked up, if any.
+#[derive(Resource, Default)]
+pub struct Cursor {
+    slot: usize,
+    /// The pile, foundation or card the cursor is on, so it stays there when slots come and go.
+    on: Option<Entity>,
+    depth: usize,
+    /// The card picked up with the keyboard, a gamepad or a tap, waiting to be put down.
+    pub held: Option<Entity>,
+    /// The cursor stays hidden until it is first used, so it doesn't get in the way of the mouse.
+    visible: bool,
+}
+
+impl Cursor {
+    /// Where the cursor is in `slots`, following the slot it was on if that moved.
+    fn index(&self, slots: &[Slot]) -> usize {
+        self.on
+            .and_then(|on| slots.iter().position(|slot| slot.root == on))
+            .unwrap_or(self.slot)
+            .min(slots.len().saturating_sub(1))
+    }
+
+    fn select(&mut self, slots: &[Slot], index: usize) {
+        self.slot = index;
+        self.on = slots.get(index).map(|slot| slot.root);
+    }
+}
+
+#[derive(Component)]
+struct CursorMarker;
+
+#[derive(Component)]
+struct HeldMarker;
+
+/// A place the cursor can stop.
+pub struct Slot {
+    /// The stock, waste, foundation, pile base, reserve or peak card the slot belongs to.
+    pub root: Entity,
+    /// What a held card is moved onto: the top card, or the empty pile or foundation itself.
+    pub target: Entity,
+    /// The face-up cards that can be picked up here, from the bottom of the pile up.
+    pub cards: Vec<Entity>,
+    /// Set for the stock, where selecting draws a card rather than picking one up.
+    pub draws: Option<Entity>,
+}
+
+#[derive(SystemParam)]
+pub struct Slots<'w, 's> {
+    q_stock: Query<'w, 's, (Entity, Option<&'static Children>), With<Stock>>,
+    q_waste: Query<'w, 's, Entity, With<Waste>>,
+    q_foundations: Query<'w, 's, Entity, With<Foundation>>,
+    q_bases: Query<'w, 's, (Entity, &'static Children), With<Base>>,
+    q_reserve: Query<'w, 's, Entity, With<Reserve>>,
+    q_peaks: Query<'w, 's, &'static Children, With<Peaks>>,
+    q_children: Query<'w, 's, &'static Children>,
+    q_pickable: Query<'w, 's, &'static Pickable>,
+    q_transforms: Query<'w, 's, &'static GlobalTransform>,
+    q_places: Query<'w, 's, &'static Place>,
+}
+
+impl Slots<'_, '_> {
+    fn is_face_up(&self, card: Entity) -> bool {
+        self.q_pickable.get(card).is_ok_and(|pickable| *pickable != Pickable::IGNORE)
+    }
+
+    fn top_of(&self, entity: Entity) -> Slot {
+        let top_card = self.q_children.iter_descendants(entity).last();
+        Slot {
+            root: entity,
+            target: top_card.unwrap_or(entity),
+            cards: top_card.into_iter().collect(),
+            draws: None,
+        }
+    }
+
+    /// Every slot on the table, left to right along each row, top row first.
+    pub fn collect(&self) -> Vec<Slot> {
+        let mut slots = vec![];
+
+        if let Ok((stock, children)) = self.q_stock.get_single() {
+            // The card on top of the stock is the one a click would land on.
+            let top_card = children.and_then(|children| children.iter().copied().max_by(|&a, &b| {
+                let z = |card| self.q_transforms.get(card).map_or(0.0, |transform| transform.translation().z);
+                z(a).total_cmp(&z(b))
+            }));
+            slots.push(Slot { root: stock, target: stock, cards: vec![], draws: Some(top_card.unwrap_or(stock)) });
+        }
+        for waste in self.q_waste.iter() {
+            slots.push(self.top_of(waste));
+        }
+        for foundation in self.q_foundations.iter() {
+            slots.push(self.top_of(foundation));
+        }
+        for reserve in self.q_reserve.iter() {
+            slots.push(self.top_of(reserve));
+        }
+        for (base, children) in self.q_bases.iter() {
+            let Some(&pile) = children.first() else {
+                continue;
+            };
+            let pile_cards: Vec<Entity> = self.q_children.iter_descendants(pile).collect();
+            slots.push(Slot {
+                root: base,
+                target: pile_cards.last().copied().unwrap_or(base),
+                cards: pile_cards.into_iter().filter(|&card| self.is_face_up(card)).collect(),
+                draws: None,
+            });
+        }
+        for peaks in self.q_peaks.iter() {
+            for &card in peaks.iter().filter(|&&card| self.is_face_up(card)) {
+                slots.push(Slot { root: card, target: card, cards: vec![card], draws: None });
+            }
+        }
+
+        // Piles are ordered by where they're based, not by their top card, which moves as they grow.
+        slots.sort_by_key(|slot| {
+            let position = self.position(slot.root);
+            match self.q_places.get(slot.root) {
+                Ok(Place::Top(_)) => (0, 0, position.x as i32),
+                Ok(Place::Tableau(_)) => (1, 0, position.x as i32),
+                Err(_) => (1, -position.y as i32, position.x as i32),
+            }
+        });
+        slots
+    }
+
+    pub fn position(&self, entity: Entity) -> Vec3 {
+        self.q_transforms.get(entity).map_or(Vec3::ZERO, |transform| transform.translation())
+    }
+}
+
+fn marker(color: Color, z: f32) -> SpriteBundle {
+    SpriteBundle {
+        sprite: Sprite {
+            color,
+            ..default()
+        },
+        transform: Transform::from_xyz(0.0, 0.0, z),
+        visibility: Visibility::Hidden,
+        ..default()
+    }
+}
+
+fn spawn_cursor(mut commands: Commands) {
+    commands.spawn((marker(Color::rgba(0.3, 0.6, 1.0, 0.4), 900.0), CursorMarker, Pickable::IGNORE));
+    commands.spawn((marker(Color::rgba(1.0, 0.85, 0.2, 0.4), 901.0), HeldMarker, Pickable::IGNORE));
+}
+
+fn keyboard_cursor(keys: Res<ButtonInput<KeyCode>>, mut cursor_action_event: EventWriter<CursorAction>) {
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if keys.just_pressed(KeyCode::Tab) {
+        cursor_action_event.send(if shift { CursorAction::Previous } else { CursorAction::Next });
+    }
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        cursor_action_event.send(CursorAction::Next);
+    }
+    if keys.just_pressed(KeyCode::ArrowLeft) {
+        cursor_action_event.send(CursorAction::Previous);
+    }
+    if keys.just_pressed(KeyCode::ArrowUp) {
+        cursor_action_event.send(CursorAction::Up);
+    }
+    if keys.just_pressed(KeyCode::ArrowDown) {
+        cursor_action_event.send(CursorAction::Down);
+    }
+    if keys.any_just_pressed([KeyCode::Enter, KeyCode::NumpadEnter, KeyCode::Space]) {
+        cursor_action_event.send(CursorAction::Select);
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        cursor_action_event.send(CursorAction::Cancel);
+    }
+    if keys.just_pressed(KeyCode::KeyH) {
+        cursor_action_event.send(CursorAction::Hint);
+    }
+}
+
+fn move_cursor(
+    mut cursor_action_event: EventReader<CursorAction>,
+    mut cursor: ResMut<Cursor>,
+    slots: Slots,
+    rules: Rules,
+    mut activate_event: EventWriter<Activate>,
+    mut move_request_event: EventWriter<MoveRequest>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    for &action in cursor_action_event.read() {
+        let slots = slots.collect();
+        if slots.is_empty() {
+            continue;
+        }
+        cursor.visible = true;
+        let index = cursor.index(&slots);
+        cursor.select(&slots, index);
+
+        match action {
+            CursorAction::Next => {
+                cursor.select(&slots, (index + 1) % slots.len());
+                cursor.depth = usize::MAX;
+            }
+            CursorAction::Previous => {
+                cursor.select(&slots, (index + slots.len() - 1) % slots.len());
+                cursor.depth = usize::MAX;
+            }
+            CursorAction::Up => {
+                let cards = slots[index].cards.len();
+                cursor.depth = cursor.depth.min(cards.saturating_sub(1)).saturating_sub(1);
+            }
+            CursorAction::Down => {
+                let cards = slots[index].cards.len();
+                cursor.depth = (cursor.depth.saturating_add(1)).min(cards.saturating_sub(1));
+            }
+            CursorAction::Select => {
+                let slot = &slots[index];
+                if let Some(card) = cursor.held.take() {
+                    move_request_event.send(MoveRequest { card, target: slot.target });
+                } else if let Some(stock) = slot.draws {
+                    activate_event.send(Activate(stock));
+                } else if let Some(&card) = slot.cards.get(cursor.depth.min(slot.cards.len().saturating_sub(1))) {
+                    if rules.variant.plays_to_waste() {
+                        activate_event.send(Activate(card));
+                    } else {
+                        cursor.held = Some(card);
+                        game_event.send(GameEvent::PickedUp);
+                    }
+                }
+            }
+            CursorAction::Cancel => {
+                cursor.held = None;
+            }
+            CursorAction::Draw => {
+                if let Some(stock) = slots.iter().find_map(|slot| slot.draws) {
+                    activate_event.send(Activate(stock));
+                }
+            }
+            CursorAction::Hint => {
+                cursor.held = None;
+                if let Some((slot, depth)) = hint(&slots, &rules) {
+                    cursor.select(&slots, slot);
+                    cursor.depth = depth;
+                } else if let Some(stock) = slots.iter().position(|slot| slot.draws.is_some()) {
+                    cursor.select(&slots, stock);
+                }
+            }
+        }
+    }
+}
+
+/// The slot and depth of the first card along the rows that can be moved somewhere useful.
+fn hint(slots: &[Slot], rules: &Rules) -> Option<(usize, usize)> {
+    slots.iter().enumerate().find_map(|(index, slot)| {
+        slot.cards.iter()
+            .position(|&card| slots.iter().any(|other| {
+                rules.destination(card, other.target).is_some_and(|destination| rules.is_progress(card, destination))
+            }))
+            .map(|depth| (index, depth))
+    })
+}
+
+#[allow(clippy::type_complexity)]
+fn draw_cursor(
+    cursor: Res<Cursor>,
+    slots: Slots,
+    layout: Res<Layout>,
+    mut q_cursor: Query<(&mut Transform, &mut Visibility, &mut Sprite), (With<CursorMarker>, Without<HeldMarker>)>,
+    mut q_held: Query<(&mut Transform, &mut Visibility, &mut Sprite), (With<HeldMarker>, Without<CursorMarker>)>,
+) {
+    // The markers stick out a little around the card they're on.
+    let marker_size = Some(layout.card + Vec2::splat(8.0));
+
+    let all_slots = slots.collect();
+    let selected = all_slots.get(cursor.index(&all_slots)).map(|slot| {
+        slot.cards.get(cursor.depth.min(slot.cards.len().saturating_sub(1))).copied().unwrap_or(slot.target)
+    });
+
+    if let Ok((mut transform, mut visibility, mut sprite)) = q_cursor.get_single_mut() {
+        sprite.custom_size = marker_size;
+        match selected.filter(|_| cursor.visible) {
+            Some(selected) => {
+                let position = slots.position(selected);
+                transform.translation = position.truncate().extend(900.0);
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+
+    if let Ok((mut transform, mut visibility, mut sprite)) = q_held.get_single_mut() {
+        sprite.custom_size = marker_size;
+        match cursor.held {
+            Some(held) => {
+                transform.translation = slots.position(held).truncate().extend(901.0);
+                *visibility = Visibility::Visible;
+            }
+            None => *visibility = Visibility::Hidden,
+        }
+    }
+}
+
+fn reset_cursor(
+    mut new_game_event: EventReader<NewGame>,
+    mut undo_event: EventReader<Undo>,
+    mut redo_event: EventReader<Redo>,
+    mut cursor: ResMut<Cursor>,
+) {
+    // Undo and redo deal the cards out again, so a held card is gone.
+    if undo_event.read().count() + redo_event.read().count() > 0 {
+        cursor.held = None;
+    }
+    if new_game_event.read().last().is_some() {
+        cursor.held = None;
+        cursor.slot = 0;
+        cursor.on = None;
+        cursor.depth = usize::MAX;
+    }
+}
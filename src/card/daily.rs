@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::score::{count_score, Score};
+use super::settings::{load, save};
+use super::win::GameWon;
+use super::NewGame;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+pub struct DailyPlugin;
+
+impl Plugin for DailyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load::<DailyResults>("daily.ron"))
+            .init_resource::<DailyGame>()
+            .insert_resource(Calendar::from(Date::today()))
+            .add_systems(Update, (score_daily.after(count_score), save_daily).chain());
+    }
+}
+
+/// A day of the calendar, in UTC so that everyone gets the same deal on the same day.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Date {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+        Date::from_days((seconds / SECONDS_PER_DAY) as i64)
+    }
+
+    /// The date `days` after 1970-01-01, by Howard Hinnant's `civil_from_days`.
+    pub fn from_days(days: i64) -> Date {
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let day_of_era = z.rem_euclid(146_097);
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+        let year = (year_of_era + era * 400) as i32 + i32::from(month <= 2);
+        Date { year, month, day }
+    }
+
+    /// Days since 1970-01-01, the inverse of `from_days`.
+    pub fn days(self) -> i64 {
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let shifted_month = i64::from((self.month + 9) % 12);
+        let day_of_year = (153 * shifted_month + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    /// Monday is 0.
+    pub fn weekday(self) -> usize {
+        // 1970-01-01 was a Thursday.
+        (self.days() + 3).rem_euclid(7) as usize
+    }
+
+    /// The shuffle for this day's deal, the same for everyone.
+    pub fn seed(self) -> u64 {
+        // SplitMix64, so that neighbouring days get unrelated deals.
+        let mut seed = (self.days() as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+        seed = (seed ^ (seed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        seed = (seed ^ (seed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        seed ^ (seed >> 31)
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// How the scored attempt at a daily deal went.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub struct DayResult {
+    pub won: bool,
+    /// Seconds of play it took to win.
+    pub seconds: f32,
+}
+
+/// The result of every daily deal played, kept between runs.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct DailyResults {
+    pub days: BTreeMap<Date, DayResult>,
+}
+
+/// Which day's deal is on the table, if it's a daily deal, and whether it's the attempt that counts.
+#[derive(Resource, Default)]
+pub struct DailyGame {
+    pub date: Option<Date>,
+    scored: bool,
+}
+
+/// The month shown on the calendar.
+#[derive(Resource, Copy, Clone, Debug, PartialEq)]
+pub struct Calendar {
+    pub year: i32,
+    pub month: u32,
+}
+
+impl From<Date> for Calendar {
+    fn from(date: Date) -> Self {
+        Calendar { year: date.year, month: date.month }
+    }
+}
+
+impl Calendar {
+    pub fn first(self) -> Date {
+        Date { year: self.year, month: self.month, day: 1 }
+    }
+
+    pub fn next(self) -> Calendar {
+        Calendar::from(Date::from_days(self.first().days() + 31))
+    }
+
+    pub fn previous(self) -> Calendar {
+        Calendar::from(Date::from_days(self.first().days() - 1))
+    }
+
+    /// Every day of the month in order.
+    pub fn days(self) -> impl Iterator<Item = Date> {
+        let first = self.first().days();
+        (first..first + 31).map(Date::from_days).take_while(move |date| date.month == self.month)
+    }
+}
+
+/// Only the first deal of each day is scored, and it counts as lost until it's won.
+fn score_daily(
+    mut new_game_event: EventReader<NewGame>,
+    mut game_won_event: EventReader<GameWon>,
+    score: Res<Score>,
+    mut daily_game: ResMut<DailyGame>,
+    mut results: ResMut<DailyResults>,
+) {
+    if let Some(&new_game) = new_game_event.read().last() {
+        daily_game.scored = false;
+        match daily_game.date {
+            Some(date) if new_game == NewGame::Deal && !results.days.contains_key(&date) => {
+                results.days.insert(date, DayResult::default());
+                daily_game.scored = true;
+            }
+            _ => {}
+        }
+    }
+    if game_won_event.read().last().is_some() && daily_game.scored {
+        daily_game.scored = false;
+        if let Some(date) = daily_game.date {
+            results.days.insert(date, DayResult { won: true, seconds: score.seconds });
+        }
+    }
+}
+
+fn save_daily(results: Res<DailyResults>) {
+    if results.is_changed() && !results.is_added() {
+        save("daily.ron", &*results);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_count_days_from_1970() {
+        assert_eq!(Date::from_days(0), Date { year: 1970, month: 1, day: 1 });
+        assert_eq!(Date::from_days(-1), Date { year: 1969, month: 12, day: 31 });
+        assert_eq!(Date::from_days(11_016), Date { year: 2000, month: 2, day: 29 });
+        assert_eq!(Date::from_days(19_782), Date { year: 2024, month: 2, day: 29 });
+        assert_eq!(Date { year: 2100, month: 3, day: 1 }.days() - Date { year: 2100, month: 2, day: 28 }.days(), 1);
+    }
+
+    #[test]
+    fn days_are_the_inverse_of_from_days() {
+        for days in (-800_000..800_000).step_by(997) {
+            assert_eq!(Date::from_days(days).days(), days);
+        }
+    }
+
+    #[test]
+    fn weekdays_start_on_monday() {
+        assert_eq!(Date { year: 1970, month: 1, day: 1 }.weekday(), 3);
+        assert_eq!(Date { year: 2024, month: 1, day: 1 }.weekday(), 0);
+        assert_eq!(Date { year: 1969, month: 12, day: 28 }.weekday(), 6);
+    }
+
+    #[test]
+    fn daily_seeds_are_fixed_and_differ_from_day_to_day() {
+        // The first SplitMix64 output for a seed of 0.
+        assert_eq!(Date::from_days(0).seed(), 0xe220_a839_7b1d_cdaf);
+        let seeds: Vec<u64> = (0..100).map(|days| Date::from_days(days).seed()).collect();
+        assert!(seeds.windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn calendars_list_every_day_of_the_month() {
+        assert_eq!(Calendar { year: 2024, month: 2 }.days().count(), 29);
+        assert_eq!(Calendar { year: 2023, month: 2 }.days().count(), 28);
+        assert_eq!(Calendar { year: 2023, month: 12 }.next(), Calendar { year: 2024, month: 1 });
+        assert_eq!(Calendar { year: 2024, month: 1 }.previous(), Calendar { year: 2023, month: 12 });
+    }
+}
@@ -0,0 +1,220 @@
+use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_mod_picking::focus::HoverMap;
+use bevy_mod_picking::prelude::*;
+
+use super::cursor::Slots;
+use super::layout::Layout;
+use super::rules::Rules;
+use super::systems::{drags, GameEvent, MoveRequest};
+use super::{Card, GameState, Input};
+
+/// How far up the dragged cards are drawn, above everything else on the table.
+const DRAG_Z: f32 = 1000.0;
+/// Drop targets are marked over the cards, under the dragged ones and the cursor.
+const TARGET_Z: f32 = 899.0;
+const TARGET: Color = Color::rgba(0.3, 1.0, 0.4, 0.25);
+const TARGET_HOVERED: Color = Color::rgba(0.3, 1.0, 0.4, 0.55);
+
+pub struct DragPlugin;
+
+impl Plugin for DragPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Dragged>()
+            .add_systems(Startup, spawn_shadow)
+            .add_systems(Update, (start_drag, drag_cards, mark_hovered_target, end_drag).chain().in_set(Input).run_if(drags))
+            .add_systems(OnExit(GameState::Playing), drop_dragged)
+            // The shadow follows where the cards are drawn, which is only known once their
+            // transforms have been propagated.
+            .add_systems(PostUpdate, follow_dragged.after(TransformSystem::TransformPropagate));
+    }
+}
+
+/// The card being dragged, which carries the cards on top of it along.
+#[derive(Resource, Default)]
+pub struct Dragged(pub Option<Entity>);
+
+#[derive(Component)]
+struct Shadow;
+
+/// Marks somewhere the dragged card can be dropped.
+#[derive(Component)]
+struct DropTarget(Entity);
+
+/// Only cards the rules let be picked up can be dragged. The card stops being picked while
+/// it's dragged, so that it's dropped on what's under it.
+fn start_drag(
+    mut commands: Commands,
+    mut drag_start_event: EventReader<Pointer<DragStart>>,
+    rules: Rules,
+    slots: Slots,
+    layout: Res<Layout>,
+    mut dragged: ResMut<Dragged>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    for drag_start in drag_start_event.read() {
+        let card = drag_start.target;
+        if !rules.can_pick_up(card) {
+            continue;
+        }
+        let targets = slots.collect().into_iter()
+            .map(|slot| slot.target)
+            .filter(|&target| rules.destination(card, target).is_some());
+        for target in targets {
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite { color: TARGET, custom_size: Some(layout.card + Vec2::splat(8.0)), ..default() },
+                    transform: Transform::from_translation(slots.position(target).truncate().extend(TARGET_Z)),
+                    ..default()
+                },
+                DropTarget(target),
+                Pickable::IGNORE,
+            ));
+        }
+        commands.entity(card).insert(Pickable::IGNORE);
+        dragged.0 = Some(card);
+        game_event.send(GameEvent::PickedUp);
+    }
+}
+
+fn drag_cards(
+    mut drag_event: EventReader<Pointer<Drag>>,
+    dragged: Res<Dragged>,
+    q_parent: Query<&Parent>,
+    q_global: Query<&GlobalTransform>,
+    mut q_transforms: Query<&mut Transform, With<Card>>,
+) {
+    let Some(card) = dragged.0 else {
+        drag_event.clear();
+        return;
+    };
+    let below = q_parent.get(card).ok().and_then(|parent| q_global.get(parent.get()).ok());
+    for drag in drag_event.read().filter(|drag| drag.target == card) {
+        if let Ok(mut transform) = q_transforms.get_mut(card) {
+            transform.translation.x += drag.delta.x;
+            transform.translation.y -= drag.delta.y;
+            transform.translation.z = DRAG_Z - below.map_or(0.0, |below| below.translation().z);
+        }
+    }
+}
+
+/// The marked target the dragged card covers most of, if it covers any of one.
+fn most_covered(card: Entity, q_global: &Query<&GlobalTransform, With<Card>>, layout: &Layout, targets: impl Iterator<Item = (Entity, Vec2)>) -> Option<Entity> {
+    let at = q_global.get(card).ok()?.translation().truncate();
+    targets
+        .map(|(target, position)| {
+            let overlap = (layout.card - (at - position).abs()).max(Vec2::ZERO);
+            (target, overlap.x * overlap.y)
+        })
+        .filter(|&(_, area)| area > 0.0)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(target, _)| target)
+}
+
+/// Picks out the target the card would land on if it were dropped now.
+fn mark_hovered_target(
+    dragged: Res<Dragged>,
+    hover_map: Res<HoverMap>,
+    rules: Rules,
+    q_global: Query<&GlobalTransform, With<Card>>,
+    mut q_targets: Query<(&DropTarget, &mut Sprite, &mut Transform)>,
+    layout: Res<Layout>,
+) {
+    let Some(card) = dragged.0 else {
+        return;
+    };
+    let covered = most_covered(card, &q_global, &layout, q_targets.iter().map(|(&DropTarget(target), _, transform)| (target, transform.translation.truncate())));
+    let hovered = covered.and_then(|target| rules.destination(card, target)).or_else(|| {
+        hover_map.values()
+            .flat_map(|hits| hits.keys())
+            .find_map(|&entity| rules.destination(card, entity))
+    });
+    for (&DropTarget(target), mut sprite, mut transform) in q_targets.iter_mut() {
+        let emphasised = hovered.is_some() && rules.destination(card, target) == hovered;
+        // The emphasised marker is a little bigger, so it's drawn over its neighbours.
+        let (colour, margin, z) = if emphasised { (TARGET_HOVERED, 16.0, TARGET_Z + 0.5) } else { (TARGET, 8.0, TARGET_Z) };
+        if sprite.color != colour {
+            sprite.color = colour;
+            sprite.custom_size = Some(layout.card + Vec2::splat(margin));
+            transform.translation.z = z;
+        }
+    }
+}
+
+/// Drops the card on the marked target it covers most of, or else whatever the pointer is over.
+#[allow(clippy::too_many_arguments)]
+fn end_drag(
+    commands: Commands,
+    mut drag_drop_event: EventReader<Pointer<Drop>>,
+    mut drag_end_event: EventReader<Pointer<DragEnd>>,
+    dragged: ResMut<Dragged>,
+    layout: Res<Layout>,
+    q_global: Query<&GlobalTransform, With<Card>>,
+    q_marked: Query<(&DropTarget, &Transform)>,
+    q_targets: Query<Entity, With<DropTarget>>,
+    mut move_request_event: EventWriter<MoveRequest>,
+) {
+    let dropped_on = drag_drop_event.read()
+        .filter(|drop| Some(drop.dropped) == dragged.0)
+        .map(|drop| drop.target)
+        .last();
+    let Some(card) = dragged.0.filter(|&card| drag_end_event.read().any(|drag_end| drag_end.target == card)) else {
+        return;
+    };
+    let covered = most_covered(card, &q_global, &layout, q_marked.iter().map(|(&DropTarget(target), transform)| (target, transform.translation.truncate())));
+    if let Some(target) = covered.or(dropped_on) {
+        move_request_event.send(MoveRequest { card, target });
+    }
+    drop_dragged(commands, dragged, q_targets);
+}
+
+/// Lets go of whatever was being dragged, which also happens on pausing or leaving the game.
+fn drop_dragged(mut commands: Commands, mut dragged: ResMut<Dragged>, q_targets: Query<Entity, With<DropTarget>>) {
+    if let Some(mut card) = dragged.0.take().and_then(|card| commands.get_entity(card)) {
+        card.try_insert(Pickable::default());
+    }
+    for target in q_targets.iter() {
+        commands.entity(target).despawn();
+    }
+}
+
+fn spawn_shadow(mut commands: Commands) {
+    commands.spawn((
+        SpriteBundle {
+            sprite: Sprite { color: Color::rgba(0.0, 0.0, 0.0, 0.35), ..default() },
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Shadow,
+        Pickable::IGNORE,
+    ));
+}
+
+/// Puts the shadow under the whole of the dragged stack, a little down and to the side.
+#[allow(clippy::type_complexity)]
+fn follow_dragged(
+    dragged: Res<Dragged>,
+    layout: Res<Layout>,
+    q_children: Query<&Children>,
+    q_cards: Query<&GlobalTransform, With<Card>>,
+    mut q_shadow: Query<(&mut Transform, &mut GlobalTransform, &mut Sprite, &mut Visibility), (With<Shadow>, Without<Card>)>,
+) {
+    let Ok((mut transform, mut global, mut sprite, mut visibility)) = q_shadow.get_single_mut() else {
+        return;
+    };
+    let stack: Vec<Vec3> = dragged.0.into_iter()
+        .flat_map(|card| std::iter::once(card).chain(q_children.iter_descendants(card)))
+        .filter_map(|card| q_cards.get(card).ok())
+        .map(GlobalTransform::translation)
+        .collect();
+    let Some(&bottom) = stack.first() else {
+        visibility.set_if_neq(Visibility::Hidden);
+        return;
+    };
+    let (low, high) = stack.iter().fold((bottom.y, bottom.y), |(low, high), card| (low.min(card.y), high.max(card.y)));
+    let offset = layout.card.x * 0.06;
+    *transform = Transform::from_xyz(bottom.x + offset, (low + high) / 2.0 - offset, bottom.z - 0.5);
+    *global = GlobalTransform::from(*transform);
+    sprite.custom_size = Some(Vec2::new(layout.card.x, layout.card.y + high - low));
+    visibility.set_if_neq(Visibility::Inherited);
+}
@@ -1,43 +1,88 @@
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
+use strum::IntoEnumIterator;
 
-use super::{Board, Card, CardSuit};
+use super::layout::Place;
+use super::{Board, Card, CardBundle, CardFace, CardSuit};
 
-#[derive(Component)]
-pub struct Foundation(pub CardSuit);
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct Foundation {
+    pub suit: CardSuit,
+    /// The rank the foundation starts from, wrapping around from king to ace.
+    pub base: CardFace,
+}
 
-pub fn spawn_foundations(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    
-    spawn_foundation(
-        commands, 
-        CardSuit::Hearts, 
-        asset_server.load("cards/Hearts 1.png"), 
-        Transform::from_xyz(-50.0, 275.0, -10.0)
-    );
+impl Foundation {
+    /// Whether `card` can be placed on this foundation, on top of `top` if it isn't empty.
+    pub fn accepts(&self, top: Option<&Card>, card: &Card) -> bool {
+        let Card::Suited { suit, face } = *card else {
+            return false;
+        };
+        suit == self.suit && match top.and_then(|top| top.face()) {
+            None => face == self.base,
+            Some(top_face) => {
+                let next = top_face.0 % 13 + 1;
+                next != self.base.0 && face.0 == next
+            }
+        }
+    }
+}
 
-    spawn_foundation(commands,
-        CardSuit::Diamonds,
-        asset_server.load("cards/Diamonds 1.png"),
-        Transform::from_xyz(100.0, 275.0, -10.0)
-    );
+/// Spawns a foundation for each suit of each deck in play, built up from the rank of
+/// the `first` card dealt to them if there is one.
+pub fn spawn_foundations(commands: &mut Commands, mut first: Option<Card>, decks: usize, first_column: usize, asset_server: &Res<AssetServer>) {
+    let base = first.and_then(|card| card.face()).unwrap_or(CardFace(1));
 
-    spawn_foundation(commands,
-        CardSuit::Clubs,
-        asset_server.load("cards/Clubs 1.png"),
-        Transform::from_xyz(250.0, 275.0, -10.0)
-    );
+    for (i, suit) in (0..decks).flat_map(|_| CardSuit::iter()).enumerate() {
+        let foundation = spawn_foundation(
+            commands,
+            Foundation { suit, base },
+            asset_server.load(Card::Suited { suit, face: base }.texture()),
+            Place::Top((first_column + i) as f32)
+        );
 
-    spawn_foundation(commands,
-        CardSuit::Spades,
-        asset_server.load("cards/Spades 1.png"),
-        Transform::from_xyz(400.0, 275.0, -10.0)
+        // Two decks hold two cards of every value, so only deal the first card once.
+        if let Some(card) = first.take_if(|card| card.suit() == Some(suit)) {
+            spawn_foundation_cards(commands, foundation, vec![card], asset_server);
+        }
+    }
+}
+
+/// Puts back a foundation that was taken off the table, with `cards` on it from the bottom up.
+pub fn restore_foundation(commands: &mut Commands, foundation: Foundation, place: Place, cards: Vec<Card>, asset_server: &Res<AssetServer>) {
+    let entity = spawn_foundation(
+        commands,
+        foundation,
+        asset_server.load(Card::Suited { suit: foundation.suit, face: foundation.base }.texture()),
+        place
     );
+    spawn_foundation_cards(commands, entity, cards, asset_server);
 }
 
-fn spawn_foundation(commands: &mut Commands, suit: CardSuit, texture: Handle<Image>, transform: Transform) {
+/// Stacks `cards` on a foundation, each one a child of the card below it.
+fn spawn_foundation_cards(commands: &mut Commands, foundation: Entity, cards: Vec<Card>, asset_server: &Res<AssetServer>) {
+    let mut parent = foundation;
+    for card in cards {
+        let child = commands.spawn((
+            CardBundle {
+                card,
+                sprite: SpriteBundle {
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    texture: asset_server.load(card.texture()),
+                    ..default()
+                },
+                pickable_bundle: PickableBundle::default()
+            },
+        )).id();
+        commands.entity(parent).add_child(child);
+        parent = child;
+    }
+}
+
+fn spawn_foundation(commands: &mut Commands, foundation: Foundation, texture: Handle<Image>, place: Place) -> Entity {
     commands.spawn((
         SpriteBundle {
-            transform,
+            transform: Transform::from_xyz(0.0, 0.0, -10.0),
             texture,
             sprite: Sprite {
                 color: Color::Rgba { red: 0.5, green: 0.5, blue: 0.5, alpha: 0.25 },
@@ -45,11 +90,10 @@ fn spawn_foundation(commands: &mut Commands, suit: CardSuit, texture: Handle<Ima
             },
             ..default()
         },
-        On::<Pointer<DragStart>>::target_insert(Pickable::IGNORE), // Disable picking
-        On::<Pointer<DragEnd>>::target_insert(Pickable::default()), // Re-enable picking
-        Foundation(suit),
+        foundation,
+        place,
         Board,
-    ));
+    )).id()
 }
 
 pub fn format_foundation(
@@ -79,4 +123,45 @@ pub fn format_foundation(
             } 
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::card::JokerColour;
+
+    fn card(suit: CardSuit, face: u8) -> Card {
+        Card::Suited { suit, face: CardFace(face) }
+    }
+
+    #[test]
+    fn builds_up_in_suit_from_the_ace() {
+        let foundation = Foundation { suit: CardSuit::Hearts, base: CardFace(1) };
+        assert!(foundation.accepts(None, &card(CardSuit::Hearts, 1)));
+        assert!(!foundation.accepts(None, &card(CardSuit::Hearts, 2)));
+        assert!(!foundation.accepts(None, &card(CardSuit::Spades, 1)));
+        assert!(foundation.accepts(Some(&card(CardSuit::Hearts, 4)), &card(CardSuit::Hearts, 5)));
+        assert!(!foundation.accepts(Some(&card(CardSuit::Hearts, 4)), &card(CardSuit::Hearts, 6)));
+        assert!(!foundation.accepts(Some(&card(CardSuit::Hearts, 4)), &card(CardSuit::Diamonds, 5)));
+        assert!(!foundation.accepts(Some(&card(CardSuit::Hearts, 13)), &card(CardSuit::Hearts, 1)));
+    }
+
+    #[test]
+    fn wraps_from_king_to_ace_above_another_base() {
+        let foundation = Foundation { suit: CardSuit::Clubs, base: CardFace(9) };
+        assert!(foundation.accepts(None, &card(CardSuit::Clubs, 9)));
+        assert!(!foundation.accepts(None, &card(CardSuit::Clubs, 1)));
+        assert!(foundation.accepts(Some(&card(CardSuit::Clubs, 13)), &card(CardSuit::Clubs, 1)));
+        assert!(foundation.accepts(Some(&card(CardSuit::Clubs, 1)), &card(CardSuit::Clubs, 2)));
+        // The card below the base finishes the foundation, so nothing wraps round onto it.
+        assert!(!foundation.accepts(Some(&card(CardSuit::Clubs, 8)), &card(CardSuit::Clubs, 9)));
+    }
+
+    #[test]
+    fn never_takes_jokers() {
+        let foundation = Foundation { suit: CardSuit::Spades, base: CardFace(1) };
+        let joker = Card::Joker { colour: JokerColour::Black, stands_for: Some((CardSuit::Spades, CardFace(1))) };
+        assert!(!foundation.accepts(None, &joker));
+        assert!(!foundation.accepts(Some(&card(CardSuit::Spades, 1)), &Card::Joker { colour: JokerColour::Black, stands_for: None }));
+    }
+}
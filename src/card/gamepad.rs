@@ -0,0 +1,93 @@
+use bevy::prelude::*;
+
+use super::cursor::CursorAction;
+use super::history::{Redo, Undo};
+
+/// How far the stick has to be pushed before it moves the cursor.
+const STICK_DEADZONE: f32 = 0.5;
+/// Seconds a direction has to be held before the cursor starts repeating.
+const REPEAT_DELAY: f32 = 0.4;
+/// Seconds between repeats while a direction stays held.
+const REPEAT_INTERVAL: f32 = 0.15;
+
+/// The direction held on the D-pad or stick and how long until it repeats.
+#[derive(Default)]
+pub struct HeldDirection {
+    action: Option<CursorAction>,
+    repeat_in: f32,
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn gamepad_cursor(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    time: Res<Time>,
+    mut held: Local<HeldDirection>,
+    mut cursor_action_event: EventWriter<CursorAction>,
+    mut undo_event: EventWriter<Undo>,
+    mut redo_event: EventWriter<Redo>,
+) {
+    let mut direction = None;
+    for gamepad in gamepads.iter() {
+        let just_pressed = |button_type| buttons.just_pressed(GamepadButton::new(gamepad, button_type));
+        let pressed = |button_type| buttons.pressed(GamepadButton::new(gamepad, button_type));
+
+        for (button_type, action) in [
+            (GamepadButtonType::South, CursorAction::Select),
+            (GamepadButtonType::East, CursorAction::Cancel),
+            (GamepadButtonType::West, CursorAction::Draw),
+            (GamepadButtonType::North, CursorAction::Hint),
+        ] {
+            if just_pressed(button_type) {
+                cursor_action_event.send(action);
+            }
+        }
+        if just_pressed(GamepadButtonType::LeftTrigger) {
+            undo_event.send(Undo);
+        }
+        if just_pressed(GamepadButtonType::RightTrigger) {
+            redo_event.send(Redo);
+        }
+
+        let stick = Vec2::new(
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0),
+            axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.0),
+        );
+        let stick_direction = if stick.length() < STICK_DEADZONE {
+            None
+        } else if stick.x.abs() > stick.y.abs() {
+            Some(if stick.x > 0.0 { CursorAction::Next } else { CursorAction::Previous })
+        } else {
+            Some(if stick.y > 0.0 { CursorAction::Up } else { CursorAction::Down })
+        };
+
+        direction = direction.or(if pressed(GamepadButtonType::DPadRight) {
+            Some(CursorAction::Next)
+        } else if pressed(GamepadButtonType::DPadLeft) {
+            Some(CursorAction::Previous)
+        } else if pressed(GamepadButtonType::DPadUp) {
+            Some(CursorAction::Up)
+        } else if pressed(GamepadButtonType::DPadDown) {
+            Some(CursorAction::Down)
+        } else {
+            stick_direction
+        });
+    }
+
+    match direction {
+        Some(action) if held.action != Some(action) => {
+            cursor_action_event.send(action);
+            held.action = Some(action);
+            held.repeat_in = REPEAT_DELAY;
+        }
+        Some(action) => {
+            held.repeat_in -= time.delta_seconds();
+            if held.repeat_in <= 0.0 {
+                cursor_action_event.send(action);
+                held.repeat_in += REPEAT_INTERVAL;
+            }
+        }
+        None => held.action = None,
+    }
+}
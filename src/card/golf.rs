@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use super::rules::Rules;
+use super::stock::Stock;
+use super::systems::{Activate, Destination, GameEvent};
+use super::variant::Variant;
+use super::waste::Waste;
+use super::{Card, GameState, NewGame};
+
+/// Chain of cards played to the waste without drawing from the stock.
+/// Each card played scores the length of the chain so far on top of its points.
+#[derive(Resource, Default, Copy, Clone, Debug, PartialEq)]
+pub struct Streak {
+    pub current: u32,
+    pub best: u32,
+}
+
+#[derive(Component)]
+pub struct StreakText;
+
+#[allow(clippy::too_many_arguments)]
+pub fn play_to_waste(
+    mut commands: Commands,
+    mut activate_event: EventReader<Activate>,
+    mut streak: ResMut<Streak>,
+    rules: Rules,
+    q_waste: Query<Entity, With<Waste>>,
+    q_stock: Query<(), With<Stock>>,
+    q_parent: Query<&Parent>,
+    mut q_transforms: Query<&mut Transform, With<Card>>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    for &Activate(target) in activate_event.read() {
+        if q_parent.get(target).is_ok_and(|parent| q_stock.contains(parent.get())) {
+            streak.current = 0;
+            continue;
+        }
+
+        let Ok(waste) = q_waste.get_single() else {
+            continue;
+        };
+        let Some(top_card) = rules.destination(target, waste) else {
+            if q_transforms.contains(target) {
+                game_event.send(GameEvent::Rejected);
+            }
+            continue;
+        };
+
+        commands.entity(top_card).add_child(target);
+        if let Ok(mut transform) = q_transforms.get_mut(target) {
+            *transform = Transform::from_xyz(0.0, 0.0, 1.0);
+        }
+        game_event.send(GameEvent::Moved { to: Destination::Waste, run: 1 });
+
+        streak.current += 1;
+        streak.best = streak.best.max(streak.current);
+    }
+}
+
+pub fn reset_streak(mut new_game_event: EventReader<NewGame>, mut streak: ResMut<Streak>) {
+    if new_game_event.read().last().is_some() {
+        *streak = Streak::default();
+    }
+}
+
+pub fn spawn_streak_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", TextStyle { font_size: 24.0, color: Color::WHITE, ..default() })
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(16.0),
+                top: Val::Px(16.0),
+                ..default()
+            }),
+        StreakText,
+    ));
+}
+
+/// The streak so far, while playing a variant that plays to the waste.
+pub fn show_streak(
+    state: Res<State<GameState>>,
+    variant: Res<Variant>,
+    streak: Res<Streak>,
+    mut q_text: Query<(&mut Text, &mut Visibility), With<StreakText>>,
+) {
+    let shown = variant.plays_to_waste() && matches!(state.get(), GameState::Playing | GameState::Won);
+    for (mut text, mut visibility) in q_text.iter_mut() {
+        visibility.set_if_neq(if shown { Visibility::Inherited } else { Visibility::Hidden });
+        let value = format!("Streak {} (best {})", streak.current, streak.best);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
@@ -0,0 +1,212 @@
+use bevy::prelude::*;
+
+use super::golf::Streak;
+use super::score::{count_score, Score};
+use super::systems::GameEvent;
+use super::table::{spawn_table, Table, Tables};
+use super::{Board, Dealing, Gameplay, Input, NewGame};
+
+pub struct HistoryPlugin;
+
+impl Plugin for HistoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<History>()
+            .add_event::<Undo>()
+            .add_event::<Redo>()
+            .add_systems(Update, keyboard_history.in_set(Input))
+            .add_systems(Update, (clear_history, undo_redo).chain().in_set(Dealing))
+            .add_systems(Update, record_history.after(Gameplay).after(count_score));
+    }
+}
+
+/// Take back the last move.
+#[derive(Event)]
+pub struct Undo;
+
+/// Make the last move taken back again.
+#[derive(Event)]
+pub struct Redo;
+
+/// Every position of the current deal, so that moves can be taken back and replayed.
+#[derive(Resource, Default)]
+pub struct History {
+    undo: Vec<Snapshot>,
+    redo: Vec<Snapshot>,
+    current: Option<Snapshot>,
+    /// The position the deal started from, kept when it is restarted.
+    start: Option<Table>,
+}
+
+/// A position and the score it was reached with, so that taking back a move takes back its points too.
+#[derive(Clone)]
+struct Snapshot {
+    table: Table,
+    points: u32,
+    moves: u32,
+    streak: Streak,
+}
+
+impl History {
+    /// Whether any moves have been made since the deal started, even if they were taken back.
+    pub fn has_moves(&self) -> bool {
+        !self.undo.is_empty() || !self.redo.is_empty()
+    }
+
+    pub fn start(&self) -> Option<&Table> {
+        self.start.as_ref()
+    }
+}
+
+fn keyboard_history(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut undo_event: EventWriter<Undo>,
+    mut redo_event: EventWriter<Redo>,
+) {
+    let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    if ctrl && keys.just_pressed(KeyCode::KeyZ) {
+        if shift {
+            redo_event.send(Redo);
+        } else {
+            undo_event.send(Undo);
+        }
+    }
+    if ctrl && keys.just_pressed(KeyCode::KeyY) {
+        redo_event.send(Redo);
+    }
+}
+
+fn clear_history(mut new_game_event: EventReader<NewGame>, mut history: ResMut<History>) {
+    match new_game_event.read().last() {
+        Some(NewGame::Deal | NewGame::Load) => *history = History::default(),
+        Some(NewGame::Restart) => *history = History { start: history.start.take(), ..default() },
+        None => {}
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn undo_redo(
+    mut commands: Commands,
+    mut undo_event: EventReader<Undo>,
+    mut redo_event: EventReader<Redo>,
+    mut history: ResMut<History>,
+    mut score: ResMut<Score>,
+    mut streak: ResMut<Streak>,
+    q_board: Query<Entity, With<Board>>,
+    asset_server: Res<AssetServer>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    let mut restored = None;
+    for _ in undo_event.read() {
+        if let Some(snapshot) = history.undo.pop() {
+            if let Some(current) = history.current.replace(snapshot.clone()) {
+                history.redo.push(current);
+            }
+            game_event.send(GameEvent::Undone);
+            restored = Some(snapshot);
+        }
+    }
+    for _ in redo_event.read() {
+        if let Some(snapshot) = history.redo.pop() {
+            if let Some(current) = history.current.replace(snapshot.clone()) {
+                history.undo.push(current);
+            }
+            game_event.send(GameEvent::Redone);
+            restored = Some(snapshot);
+        }
+    }
+
+    if let Some(snapshot) = restored {
+        for entity in q_board.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        spawn_table(&mut commands, &snapshot.table, &asset_server);
+        score.points = snapshot.points;
+        score.moves = snapshot.moves;
+        *streak = snapshot.streak;
+    }
+}
+
+/// Remembers the position whenever a move has changed it.
+fn record_history(tables: Tables, score: Res<Score>, streak: Res<Streak>, mut history: ResMut<History>) {
+    let table = tables.capture();
+    if table.is_empty() || history.current.as_ref().is_some_and(|current| current.table == table) {
+        return;
+    }
+    if history.start.is_none() {
+        history.start = Some(table.clone());
+    }
+    if let Some(previous) = history.current.replace(Snapshot { table, points: score.points, moves: score.moves, streak: *streak }) {
+        history.undo.push(previous);
+        history.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+    use strum::IntoEnumIterator;
+
+    use super::*;
+    use crate::card::peaks::{flip_exposed_peaks, spawn_peaks, PeakSlot, Peaks};
+    use crate::card::waste::{spawn_waste, Waste};
+    use crate::card::win::GameWon;
+    use crate::card::{Card, CardFace, CardSuit};
+
+    fn deal_tri_peaks(mut commands: Commands, asset_server: Res<AssetServer>) {
+        let mut deck = CardSuit::iter().flat_map(|suit| (1..14).map(move |face| Card::Suited { suit, face: CardFace(face) }));
+        spawn_peaks(&mut commands, deck.by_ref().take(28).collect(), &asset_server);
+        spawn_waste(&mut commands, deck.take(1), &asset_server);
+    }
+
+    /// Plays the first two cards of the bottom row to the waste, which uncovers one card above them.
+    fn play_two_cards(
+        mut commands: Commands,
+        q_waste: Query<Entity, With<Waste>>,
+        q_peaks: Query<&Children, With<Peaks>>,
+        q_slots: Query<&PeakSlot>,
+        q_children: Query<&Children>,
+    ) {
+        let waste = q_waste.single();
+        let mut top_card = q_children.iter_descendants(waste).last().unwrap_or(waste);
+        for number in [18, 19] {
+            let card = q_peaks.single().iter()
+                .copied()
+                .find(|&card| q_slots.get(card).is_ok_and(|slot| slot.number() == number))
+                .unwrap();
+            commands.entity(top_card).add_child(card);
+            top_card = card;
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_put_back_the_points_without_adding_any() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Image>()
+            .init_resource::<History>()
+            .init_resource::<Score>()
+            .init_resource::<Streak>()
+            .add_event::<Undo>()
+            .add_event::<Redo>()
+            .add_event::<NewGame>()
+            .add_event::<GameEvent>()
+            .add_event::<GameWon>()
+            .add_systems(Update, (undo_redo, flip_exposed_peaks, count_score, record_history).chain());
+        app.world.run_system_once(deal_tri_peaks);
+        app.update();
+        app.world.run_system_once(play_two_cards);
+        app.update();
+        assert_eq!(app.world.resource::<Score>().points, 5);
+
+        app.world.send_event(Undo);
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<Score>().points, 0);
+
+        app.world.send_event(Redo);
+        app.update();
+        app.update();
+        assert_eq!(app.world.resource::<Score>().points, 5);
+    }
+}
@@ -0,0 +1,203 @@
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use serde::{Deserialize, Serialize};
+
+use super::peaks::{PeakSlot, Peaks};
+use super::settings::Settings;
+use super::variant::Variant;
+use super::{Board, Card, Dealing, Gameplay};
+
+/// Width over height of the card images.
+const CARD_ASPECT: f32 = 103.0 / 138.0;
+
+pub struct LayoutPlugin;
+
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Layout>()
+            .add_systems(Update, update_layout.before(Gameplay))
+            .add_systems(Update, (place_board, place_peaks, size_cards).after(update_layout).after(Dealing));
+    }
+}
+
+/// Which column of the table something sits in, counted from the left.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub enum Place {
+    Top(f32),
+    Tableau(f32),
+}
+
+impl Place {
+    pub fn column(&self) -> f32 {
+        match *self {
+            Place::Top(column) | Place::Tableau(column) => column,
+        }
+    }
+}
+
+/// Which way round the table is laid out.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub enum Handedness {
+    #[default]
+    RightHanded,
+    /// Stock and waste on the right, foundations on the left.
+    LeftHanded,
+    /// As `LeftHanded`, with the tableau piles in reverse order too.
+    Mirrored,
+}
+
+impl Handedness {
+    pub fn next(self) -> Self {
+        match self {
+            Handedness::RightHanded => Handedness::LeftHanded,
+            Handedness::LeftHanded => Handedness::Mirrored,
+            Handedness::Mirrored => Handedness::RightHanded,
+        }
+    }
+
+    fn mirrors(self, place: Place) -> bool {
+        match place {
+            Place::Top(_) => self != Handedness::RightHanded,
+            Place::Tableau(_) => self == Handedness::Mirrored,
+        }
+    }
+}
+
+/// Card size and where everything goes, worked out from the window size.
+#[derive(Resource, Copy, Clone, Debug, PartialEq)]
+pub struct Layout {
+    pub card: Vec2,
+    /// Distance between the centres of neighbouring columns.
+    pub pitch: f32,
+    columns: usize,
+    handedness: Handedness,
+    /// Height of the centre of the cards on the top row and on the tableau.
+    top: f32,
+    tableau: f32,
+    /// The lowest the centre of a card can go before it runs off the bottom of the window.
+    bottom: f32,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Layout::new(Vec2::new(1280.0, 720.0), 7, Handedness::default())
+    }
+}
+
+impl Layout {
+    pub fn new(window: Vec2, columns: usize, handedness: Handedness) -> Self {
+        let columns = columns.max(2);
+        let card_width = (window.x / (columns as f32 * 1.2 + 0.6)).min(window.y / 5.0 * CARD_ASPECT);
+        let card = Vec2::new(card_width, card_width / CARD_ASPECT);
+        let margin = card.x * 0.15;
+        let pitch = (card.x * 1.45).min((window.x - 2.0 * margin - card.x) / (columns - 1) as f32);
+        let top = window.y / 2.0 - margin - card.y / 2.0;
+
+        Layout {
+            card,
+            pitch,
+            columns,
+            handedness,
+            top,
+            tableau: top - card.y - margin,
+            bottom: -window.y / 2.0 + margin + card.y / 2.0,
+        }
+    }
+
+    pub fn position(&self, place: Place) -> Vec2 {
+        let offset = (place.column() - self.centre()) * self.pitch;
+        let x = if self.handedness.mirrors(place) { -offset } else { offset };
+        match place {
+            Place::Top(_) => Vec2::new(x, self.top),
+            Place::Tableau(_) => Vec2::new(x, self.tableau),
+        }
+    }
+
+    pub fn centre(&self) -> f32 {
+        (self.columns - 1) as f32 / 2.0
+    }
+
+    /// How far apart face-up cards are fanned down a pile when there is room.
+    pub fn fan(&self) -> f32 {
+        self.card.y * 0.36
+    }
+
+    /// The closest face-up cards can be fanned and still show their rank and suit.
+    pub fn min_fan(&self) -> f32 {
+        self.card.y * 0.2
+    }
+
+    /// How far apart face-down cards are fanned when there is room.
+    pub fn fan_face_down(&self) -> f32 {
+        self.card.y * 0.12
+    }
+
+    pub fn min_fan_face_down(&self) -> f32 {
+        self.card.y * 0.05
+    }
+
+    /// How far below the first card of a pile its last card can go and still be on screen.
+    pub fn tableau_depth(&self) -> f32 {
+        self.tableau - self.bottom
+    }
+
+    /// How far the cards last drawn are fanned out on the waste, away from the stock.
+    pub fn waste_fan(&self) -> f32 {
+        let fan = self.card.x * 0.2;
+        if self.handedness.mirrors(Place::Top(0.0)) { -fan } else { fan }
+    }
+
+    /// Offset between neighbouring half-columns and rows of the TriPeaks peaks.
+    pub fn peak_step(&self) -> Vec2 {
+        Vec2::new(self.pitch / 2.0, self.card.y * 0.47)
+    }
+}
+
+fn update_layout(
+    q_window: Query<&Window, With<PrimaryWindow>>,
+    variant: Res<Variant>,
+    settings: Res<Settings>,
+    mut layout: ResMut<Layout>,
+) {
+    if let Ok(window) = q_window.get_single() {
+        layout.set_if_neq(Layout::new(Vec2::new(window.width(), window.height()), variant.columns(), settings.handedness));
+    }
+}
+
+fn place_board(layout: Res<Layout>, mut q_places: Query<(&Place, &mut Transform)>) {
+    for (&place, mut transform) in q_places.iter_mut() {
+        let position = layout.position(place);
+        if transform.translation.truncate() != position {
+            transform.translation = position.extend(transform.translation.z);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn place_peaks(
+    layout: Res<Layout>,
+    mut q_peaks: Query<(&mut Transform, &Children), (With<Peaks>, Without<PeakSlot>)>,
+    mut q_slots: Query<(&PeakSlot, &mut Transform), Without<Peaks>>,
+) {
+    for (mut transform, cards) in q_peaks.iter_mut() {
+        let position = layout.position(Place::Tableau(layout.centre()));
+        if transform.translation.truncate() != position {
+            transform.translation = position.extend(transform.translation.z);
+        }
+        // Cards played to the waste keep their slot, but are no longer the layout's to place.
+        for &card in cards {
+            if let Ok((slot, mut transform)) = q_slots.get_mut(card) {
+                transform.set_if_neq(slot.transform(&layout));
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn size_cards(layout: Res<Layout>, mut q_sprites: Query<&mut Sprite, Or<(With<Card>, With<Board>)>>) {
+    for mut sprite in q_sprites.iter_mut() {
+        if sprite.custom_size != Some(layout.card) {
+            sprite.custom_size = Some(layout.card);
+        }
+    }
+}
@@ -0,0 +1,340 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+use bevy::window::ReceivedCharacter;
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumIter};
+
+use super::daily::Date;
+use super::score::{count_score, Score};
+use super::settings::{load, save};
+use super::variant::{HouseRules, Variant};
+use super::win::GameWon;
+use super::{GameState, Seed};
+
+/// How many results each table keeps.
+const PLACES: usize = 10;
+const NAME_LENGTH: usize = 16;
+
+pub struct LeaderboardPlugin;
+
+impl Plugin for LeaderboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load::<Leaderboards>("leaderboards.ron"))
+            .init_resource::<NewRecord>()
+            .add_systems(Update, (
+                record_win.after(count_score),
+                type_name,
+                save_leaderboards,
+            ).chain())
+            // Leaving the win screen before pressing Enter keeps the name as typed so far.
+            .add_systems(OnExit(GameState::Won), enter_record);
+    }
+}
+
+/// The rules a game was played under. Each has its own tables, since their results can't be compared.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Mode {
+    pub variant: Variant,
+    pub jokers: bool,
+    #[serde(default)]
+    pub draw_three: bool,
+}
+
+impl Mode {
+    pub fn new(variant: Variant, house_rules: HouseRules) -> Mode {
+        Mode { variant, jokers: house_rules.jokers, draw_three: house_rules.draw(variant) == 3 }
+    }
+}
+
+/// What a table is ranked by.
+#[derive(EnumIter, Display, Default, Copy, Clone, Debug, PartialEq)]
+pub enum Category {
+    #[default]
+    Score,
+    Time,
+    Moves,
+}
+
+impl Category {
+    /// Points can't be ranked once moves have been taken back and made again.
+    fn ranks(self, entry: &Entry) -> bool {
+        self != Category::Score || !entry.undone
+    }
+
+    /// Best first, with the earlier of two equal results ahead.
+    fn compare(self, a: &Entry, b: &Entry) -> Ordering {
+        match self {
+            Category::Score => b.points.cmp(&a.points),
+            Category::Time => a.seconds.total_cmp(&b.seconds),
+            Category::Moves => a.moves.cmp(&b.moves),
+        }
+    }
+}
+
+/// A won game.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub name: String,
+    pub points: u32,
+    pub seconds: f32,
+    pub moves: u32,
+    pub seed: u64,
+    pub date: Date,
+    /// Moves were taken back, so the points don't count towards the score table.
+    #[serde(default)]
+    pub undone: bool,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} points, {} moves in {} by {}", self.points, self.moves, Score::time(self.seconds), self.name)
+    }
+}
+
+/// The best games of one `Mode` by each `Category`.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Tables {
+    pub score: Vec<Entry>,
+    pub time: Vec<Entry>,
+    pub moves: Vec<Entry>,
+}
+
+impl Tables {
+    pub fn table(&self, category: Category) -> &[Entry] {
+        match category {
+            Category::Score => &self.score,
+            Category::Time => &self.time,
+            Category::Moves => &self.moves,
+        }
+    }
+
+    fn table_mut(&mut self, category: Category) -> &mut Vec<Entry> {
+        match category {
+            Category::Score => &mut self.score,
+            Category::Time => &mut self.time,
+            Category::Moves => &mut self.moves,
+        }
+    }
+
+    /// Whether `entry` would make any of the tables.
+    fn qualifies(&self, entry: &Entry) -> bool {
+        [Category::Score, Category::Time, Category::Moves].into_iter().filter(|category| category.ranks(entry)).any(|category| {
+            let table = self.table(category);
+            table.len() < PLACES || table.iter().any(|place| category.compare(entry, place) == Ordering::Less)
+        })
+    }
+
+    fn insert(&mut self, entry: &Entry) {
+        for category in [Category::Score, Category::Time, Category::Moves].into_iter().filter(|category| category.ranks(entry)) {
+            let table = self.table_mut(category);
+            let place = table.partition_point(|place| category.compare(place, entry) != Ordering::Greater);
+            table.insert(place, entry.clone());
+            table.truncate(PLACES);
+        }
+    }
+}
+
+/// The high-score tables of every mode and the best result on every deal won, kept between runs.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Leaderboards {
+    pub modes: HashMap<Mode, Tables>,
+    /// The highest scoring win of each deal without an undo, by the seed it was shuffled with.
+    pub deals: HashMap<(Mode, u64), Entry>,
+    /// The name last entered, offered again for the next record.
+    pub name: String,
+}
+
+impl Leaderboards {
+    pub fn deal(&self, mode: Mode, seed: u64) -> Option<&Entry> {
+        self.deals.get(&(mode, seed))
+    }
+
+    fn insert(&mut self, mode: Mode, entry: Entry) {
+        self.modes.entry(mode).or_default().insert(&entry);
+        if !Category::Score.ranks(&entry) {
+            return;
+        }
+        let best = self.deals.entry((mode, entry.seed)).or_insert_with(|| entry.clone());
+        if Category::Score.compare(&entry, best) == Ordering::Less {
+            *best = entry;
+        }
+    }
+}
+
+/// A win good enough for the tables, waiting on the player to type their name.
+#[derive(Resource, Default)]
+pub struct NewRecord(pub Option<(Mode, Entry)>);
+
+/// Run condition for while the keyboard is typing a name rather than playing.
+pub fn entering_name(new_record: Res<NewRecord>) -> bool {
+    new_record.0.is_some()
+}
+
+/// Only games dealt from a seed count. Loaded positions and replays don't.
+#[allow(clippy::too_many_arguments)]
+fn record_win(
+    mut game_won_event: EventReader<GameWon>,
+    score: Res<Score>,
+    state: Res<State<GameState>>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    seed: Res<Seed>,
+    mut leaderboards: ResMut<Leaderboards>,
+    mut new_record: ResMut<NewRecord>,
+) {
+    if game_won_event.read().last().is_none() || !score.ranked || *state.get() == GameState::Replay {
+        return;
+    }
+    let mode = Mode::new(*variant, *house_rules);
+    let entry = Entry {
+        name: leaderboards.name.clone(),
+        points: score.points,
+        seconds: score.seconds,
+        moves: score.moves,
+        seed: seed.0,
+        date: Date::today(),
+        undone: score.undone,
+    };
+    if leaderboards.modes.get(&mode).is_none_or(|tables| tables.qualifies(&entry)) {
+        new_record.0 = Some((mode, entry));
+    } else {
+        leaderboards.insert(mode, entry);
+    }
+}
+
+/// Typed characters go into the name, Backspace takes one off and Enter puts it on the tables.
+/// Keys are read every frame, so the one that won the deal isn't taken as typing.
+fn type_name(
+    mut received_character_event: EventReader<ReceivedCharacter>,
+    mut keyboard_input_event: EventReader<KeyboardInput>,
+    state: Res<State<GameState>>,
+    mut new_record: ResMut<NewRecord>,
+    leaderboards: ResMut<Leaderboards>,
+) {
+    let typed: String = received_character_event.read()
+        .flat_map(|event| event.char.chars())
+        .filter(|character| !character.is_control())
+        .collect();
+    let pressed: Vec<KeyCode> = keyboard_input_event.read()
+        .filter(|event| event.state == ButtonState::Pressed)
+        .map(|event| event.key_code)
+        .collect();
+    if *state.get() != GameState::Won || new_record.0.is_none() || (typed.is_empty() && pressed.is_empty()) {
+        return;
+    }
+    let Some((_, entry)) = new_record.0.as_mut() else {
+        return;
+    };
+    for character in typed.chars() {
+        if entry.name.chars().count() < NAME_LENGTH {
+            entry.name.push(character);
+        }
+    }
+    if pressed.contains(&KeyCode::Backspace) {
+        entry.name.pop();
+    }
+    if pressed.iter().any(|&key| matches!(key, KeyCode::Enter | KeyCode::NumpadEnter)) {
+        enter_record(new_record, leaderboards);
+    }
+}
+
+fn enter_record(mut new_record: ResMut<NewRecord>, mut leaderboards: ResMut<Leaderboards>) {
+    let Some((mode, mut entry)) = new_record.0.take() else {
+        return;
+    };
+    entry.name = entry.name.trim().to_string();
+    if entry.name.is_empty() {
+        entry.name = "Anonymous".to_string();
+    } else {
+        leaderboards.name = entry.name.clone();
+    }
+    leaderboards.insert(mode, entry);
+}
+
+fn save_leaderboards(leaderboards: Res<Leaderboards>) {
+    if leaderboards.is_changed() && !leaderboards.is_added() {
+        save("leaderboards.ron", &*leaderboards);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(points: u32, seconds: f32, moves: u32) -> Entry {
+        Entry {
+            name: format!("{points}"),
+            points,
+            seconds,
+            moves,
+            seed: 1,
+            date: Date { year: 2024, month: 1, day: 1 },
+            undone: false,
+        }
+    }
+
+    #[test]
+    fn tables_keep_the_best_in_order() {
+        let mut tables = Tables::default();
+        for (points, seconds, moves) in [(300, 90.0, 120), (500, 200.0, 100), (400, 60.0, 150)] {
+            tables.insert(&entry(points, seconds, moves));
+        }
+        let points: Vec<u32> = tables.score.iter().map(|entry| entry.points).collect();
+        assert_eq!(points, [500, 400, 300]);
+        let seconds: Vec<f32> = tables.time.iter().map(|entry| entry.seconds).collect();
+        assert_eq!(seconds, [60.0, 90.0, 200.0]);
+        let moves: Vec<u32> = tables.moves.iter().map(|entry| entry.moves).collect();
+        assert_eq!(moves, [100, 120, 150]);
+    }
+
+    #[test]
+    fn ties_go_after_the_earlier_result() {
+        let mut tables = Tables::default();
+        tables.insert(&Entry { name: "first".to_string(), ..entry(100, 60.0, 100) });
+        tables.insert(&Entry { name: "second".to_string(), ..entry(100, 60.0, 100) });
+        assert_eq!(tables.score[0].name, "first");
+        assert_eq!(tables.time[1].name, "second");
+    }
+
+    #[test]
+    fn full_tables_only_take_better_results() {
+        let mut tables = Tables::default();
+        for i in 0..PLACES as u32 {
+            let better = entry(1000 - i, 100.0 + i as f32, 100 + i);
+            assert!(tables.qualifies(&better));
+            tables.insert(&better);
+        }
+        assert!(!tables.qualifies(&entry(1, 1000.0, 1000)));
+        assert!(tables.qualifies(&entry(1, 1000.0, 50)));
+        tables.insert(&entry(1, 1000.0, 50));
+        assert_eq!(tables.moves.len(), PLACES);
+        assert_eq!(tables.moves[0].moves, 50);
+        assert!(tables.score.iter().all(|entry| entry.points != 1));
+    }
+
+    #[test]
+    fn undone_games_stay_off_the_score_table() {
+        let mut tables = Tables::default();
+        let undone = Entry { undone: true, ..entry(900, 60.0, 80) };
+        assert!(tables.qualifies(&undone));
+        tables.insert(&undone);
+        assert!(tables.score.is_empty());
+        assert_eq!(tables.time.len(), 1);
+        assert_eq!(tables.moves.len(), 1);
+    }
+
+    #[test]
+    fn entries_saved_before_undo_was_recorded_still_load() {
+        let text = r#"(name: "old", points: 10, seconds: 5.0, moves: 3, seed: 9, date: (year: 2024, month: 2, day: 3))"#;
+        let entry: Entry = ron::from_str(text).unwrap();
+        assert!(!entry.undone);
+        assert!(ron::from_str::<Entry>(r#"(name: "bad", points: -1)"#).is_err());
+    }
+}
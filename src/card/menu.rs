@@ -0,0 +1,556 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use strum::IntoEnumIterator;
+
+use super::abandon::{Abandon, Confirm, Pending};
+use super::achievement::{Achievements, Goal, Progress};
+use super::daily::{Calendar, DailyResults, Date};
+use super::leaderboard::{Category, Entry, Leaderboards, Mode, NewRecord};
+use super::notation::LoadPosition;
+use super::replay::{Replay, ReplayControl, ReplayProgress, LAST_GAME};
+use super::score::Score;
+use super::settings::{exists, Settings};
+use super::stats::Stats;
+use super::variant::{HouseRules, Variant};
+use super::win::Won;
+use super::{Board, GameState, Seed};
+
+const BACKGROUND: Color = Color::rgba(0.05, 0.2, 0.1, 0.95);
+const BUTTON: Color = Color::rgb(0.15, 0.35, 0.2);
+const BUTTON_HOVERED: Color = Color::rgb(0.25, 0.5, 0.3);
+const BUTTON_SELECTED: Color = Color::rgb(0.6, 0.5, 0.15);
+
+pub struct MenuPlugin;
+
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Choice>()
+            .add_systems(Update, (menu_keys, press_buttons, show_screen, hide_board).chain());
+    }
+}
+
+/// The variant and house rules picked on the title screen, for the next new game.
+#[derive(Resource, Default)]
+struct Choice {
+    variant: Variant,
+    house_rules: HouseRules,
+    category: Category,
+}
+
+/// Everything on the current screen, so it can be cleared when the screen changes.
+#[derive(Component)]
+struct Screen;
+
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+enum MenuButton {
+    Variant(Variant),
+    Jokers,
+    DrawThree,
+    NewGame,
+    Restart,
+    Yes,
+    No,
+    Continue,
+    Settings,
+    Stats,
+    MainMenu,
+    Resume,
+    VolumeDown,
+    VolumeUp,
+    Mute,
+    Handedness,
+    Watch,
+    Replay(ReplayControl),
+    Load(LoadPosition),
+    Daily,
+    PlayDaily,
+    PreviousMonth,
+    NextMonth,
+    Leaderboards,
+    Category(Category),
+    Achievements,
+    Timing,
+}
+
+/// P or Start pauses and resumes, Escape backs out of a screen or a replay.
+fn menu_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let start = gamepads.iter().any(|gamepad| buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start)));
+    if keys.just_pressed(KeyCode::KeyP) || start {
+        match state.get() {
+            GameState::Playing => next_state.set(GameState::Paused),
+            GameState::Paused => next_state.set(GameState::Playing),
+            _ => {}
+        }
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        match state.get() {
+            GameState::Paused => next_state.set(GameState::Playing),
+            GameState::Settings | GameState::Stats | GameState::Replay | GameState::Daily | GameState::Leaderboards
+            | GameState::Achievements => {
+                next_state.set(GameState::MainMenu);
+            }
+            _ => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn press_buttons(
+    mut q_buttons: Query<(&Interaction, &MenuButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut abandon_event: EventWriter<Abandon>,
+    mut confirm_event: EventWriter<Confirm>,
+    mut replay_control_event: EventWriter<ReplayControl>,
+    mut load_position_event: EventWriter<LoadPosition>,
+    mut choice: ResMut<Choice>,
+    mut settings: ResMut<Settings>,
+    mut calendar: ResMut<Calendar>,
+) {
+    for (interaction, &button, mut colour) in q_buttons.iter_mut() {
+        match interaction {
+            Interaction::Hovered => *colour = BUTTON_HOVERED.into(),
+            Interaction::None => *colour = button_colour(button, &choice).into(),
+            Interaction::Pressed => match button {
+                MenuButton::Variant(chosen) => choice.variant = chosen,
+                MenuButton::Jokers => choice.house_rules.jokers = !choice.house_rules.jokers,
+                MenuButton::DrawThree => choice.house_rules.draw_three = !choice.house_rules.draw_three,
+                MenuButton::NewGame => {
+                    abandon_event.send(Abandon::NewDeal { variant: choice.variant, house_rules: choice.house_rules });
+                }
+                MenuButton::Restart => {
+                    abandon_event.send(Abandon::Restart);
+                }
+                MenuButton::Yes => {
+                    confirm_event.send(Confirm(true));
+                }
+                MenuButton::No => {
+                    confirm_event.send(Confirm(false));
+                }
+                MenuButton::Continue | MenuButton::Resume => next_state.set(GameState::Playing),
+                MenuButton::Settings => next_state.set(GameState::Settings),
+                MenuButton::Stats => next_state.set(GameState::Stats),
+                MenuButton::MainMenu => next_state.set(GameState::MainMenu),
+                MenuButton::VolumeDown => settings.volume = (settings.volume - 0.1).max(0.0),
+                MenuButton::VolumeUp => settings.volume = (settings.volume + 0.1).min(1.0),
+                MenuButton::Mute => settings.muted = !settings.muted,
+                MenuButton::Handedness => settings.handedness = settings.handedness.next(),
+                MenuButton::Watch => {
+                    abandon_event.send(Abandon::Replay);
+                }
+                MenuButton::Replay(control) => {
+                    replay_control_event.send(control);
+                }
+                MenuButton::Load(source) => {
+                    load_position_event.send(source);
+                }
+                MenuButton::Daily => next_state.set(GameState::Daily),
+                MenuButton::PlayDaily => {
+                    abandon_event.send(Abandon::Daily);
+                }
+                MenuButton::PreviousMonth => *calendar = calendar.previous(),
+                MenuButton::NextMonth => *calendar = calendar.next(),
+                MenuButton::Leaderboards => next_state.set(GameState::Leaderboards),
+                MenuButton::Category(category) => choice.category = category,
+                MenuButton::Achievements => next_state.set(GameState::Achievements),
+                MenuButton::Timing => settings.timing = settings.timing.next(),
+            },
+        }
+    }
+}
+
+fn button_colour(button: MenuButton, choice: &Choice) -> Color {
+    match button {
+        MenuButton::Variant(variant) if variant == choice.variant => BUTTON_SELECTED,
+        MenuButton::Jokers if choice.house_rules.jokers => BUTTON_SELECTED,
+        MenuButton::DrawThree if choice.house_rules.draw_three => BUTTON_SELECTED,
+        MenuButton::Category(category) if category == choice.category => BUTTON_SELECTED,
+        _ => BUTTON,
+    }
+}
+
+/// What the screens show that can change while they're up.
+#[derive(SystemParam)]
+struct Shown<'w> {
+    settings: Res<'w, Settings>,
+    stats: Res<'w, Stats>,
+    daily_results: Res<'w, DailyResults>,
+    calendar: Res<'w, Calendar>,
+    leaderboards: Res<'w, Leaderboards>,
+    new_record: Res<'w, NewRecord>,
+    achievements: Res<'w, Achievements>,
+    progress: Res<'w, Progress>,
+}
+
+impl Shown<'_> {
+    fn is_changed(&self) -> bool {
+        self.settings.is_changed() || self.stats.is_changed() || self.daily_results.is_changed()
+            || self.calendar.is_changed() || self.leaderboards.is_changed() || self.new_record.is_changed() || self.progress.is_changed()
+    }
+}
+
+/// Builds the screen for the current state, again whenever anything shown on it changes.
+#[allow(clippy::too_many_arguments)]
+fn show_screen(
+    mut commands: Commands,
+    state: Res<State<GameState>>,
+    mut choice: ResMut<Choice>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    seed: Res<Seed>,
+    score: Res<Score>,
+    won: Res<Won>,
+    pending: Res<Pending>,
+    replay: Res<Replay>,
+    shown: Shown,
+    q_board: Query<(), With<Board>>,
+    q_screen: Query<Entity, With<Screen>>,
+) {
+    if state.is_changed() {
+        *choice = Choice { variant: *variant, house_rules: *house_rules, category: choice.category };
+    }
+    if !(state.is_changed() || choice.is_changed() || shown.is_changed()) {
+        return;
+    }
+    let Shown { settings, stats, daily_results, calendar, leaderboards, new_record, achievements, progress } = shown;
+    for entity in q_screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let in_progress = !q_board.is_empty() && !won.0;
+    match state.get() {
+        GameState::Playing => {}
+        GameState::MainMenu => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            text(panel, "Solitaire", 64.0);
+            row(panel, |row| {
+                for variant in Variant::iter() {
+                    button(row, variant.to_string(), MenuButton::Variant(variant), &choice);
+                }
+            });
+            row(panel, |row| {
+                house_rule_buttons(row, &choice);
+                button(row, format!("Timer: {}", settings.timing), MenuButton::Timing, &choice);
+            });
+            row(panel, |row| {
+                if in_progress {
+                    button(row, "Continue", MenuButton::Continue, &choice);
+                }
+                button(row, "New game", MenuButton::NewGame, &choice);
+                button(row, "Daily deal", MenuButton::Daily, &choice);
+                button(row, "Settings", MenuButton::Settings, &choice);
+                button(row, "Stats", MenuButton::Stats, &choice);
+                button(row, "Leaderboards", MenuButton::Leaderboards, &choice);
+                button(row, "Achievements", MenuButton::Achievements, &choice);
+                if exists(LAST_GAME) {
+                    button(row, "Watch last game", MenuButton::Watch, &choice);
+                }
+            });
+            row(panel, |row| {
+                button(row, "Load position", MenuButton::Load(LoadPosition::File), &choice);
+                button(row, "Paste position", MenuButton::Load(LoadPosition::Clipboard), &choice);
+            });
+        }),
+        GameState::Paused => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            text(panel, "Paused", 48.0);
+            row(panel, |row| {
+                button(row, "Resume", MenuButton::Resume, &choice);
+                button(row, "Restart deal", MenuButton::Restart, &choice);
+                button(row, "New deal", MenuButton::NewGame, &choice);
+                button(row, "Main menu", MenuButton::MainMenu, &choice);
+            });
+        }),
+        GameState::Won => spawn_panel(&mut commands, Color::NONE, |panel| {
+            text(panel, format!("{} won!", *variant), 48.0);
+            text(panel, score.to_string(), 28.0);
+            if let Some((_, entry)) = &new_record.0 {
+                text(panel, "New record! Type your name and press Enter", 28.0);
+                text(panel, format!("{}_", entry.name), 36.0);
+            } else if let Some(best) = leaderboards.deal(Mode::new(*variant, *house_rules), seed.0) {
+                text(panel, format!("Best on this deal: {best}"), 24.0);
+            }
+            row(panel, |row| {
+                button(row, "New deal", MenuButton::NewGame, &choice);
+                button(row, "Play again", MenuButton::Restart, &choice);
+                button(row, "Watch replay", MenuButton::Watch, &choice);
+                button(row, "Main menu", MenuButton::MainMenu, &choice);
+            });
+        }),
+        GameState::TimeUp => spawn_panel(&mut commands, Color::NONE, |panel| {
+            text(panel, "Time's up!", 48.0);
+            row(panel, |row| {
+                button(row, "New deal", MenuButton::NewGame, &choice);
+                button(row, "Play again", MenuButton::Restart, &choice);
+                button(row, "Main menu", MenuButton::MainMenu, &choice);
+            });
+        }),
+        GameState::Replay => spawn_bar(&mut commands, |bar| {
+            bar.spawn((TextBundle::from_section(replay.progress(), TextStyle { font_size: 24.0, color: Color::WHITE, ..default() }), ReplayProgress));
+            button(bar, "Play/Pause", MenuButton::Replay(ReplayControl::Play), &choice);
+            button(bar, "Step", MenuButton::Replay(ReplayControl::Step), &choice);
+            button(bar, "Slower", MenuButton::Replay(ReplayControl::Slower), &choice);
+            button(bar, "Faster", MenuButton::Replay(ReplayControl::Faster), &choice);
+            button(bar, "Main menu", MenuButton::MainMenu, &choice);
+        }),
+        GameState::Daily => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            let today = Date::today();
+            text(panel, "Daily deal", 48.0);
+            row(panel, |row| {
+                button(row, "<", MenuButton::PreviousMonth, &choice);
+                text(row, format!("{}-{:02}", calendar.year, calendar.month), 32.0);
+                button(row, ">", MenuButton::NextMonth, &choice);
+            });
+            spawn_calendar(panel, *calendar, today, &daily_results);
+            let won = daily_results.days.values().filter(|result| result.won).count();
+            text(panel, format!("Won {won} of {} days played", daily_results.days.len()), 24.0);
+            row(panel, |row| {
+                let play = if daily_results.days.contains_key(&today) { "Practise today's deal" } else { "Play today's deal" };
+                button(row, play, MenuButton::PlayDaily, &choice);
+                button(row, "Back", MenuButton::MainMenu, &choice);
+            });
+        }),
+        GameState::Confirm => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            if let Some((abandon, _)) = pending.0 {
+                text(panel, abandon.question(), 36.0);
+            }
+            row(panel, |row| {
+                button(row, "Yes", MenuButton::Yes, &choice);
+                button(row, "No", MenuButton::No, &choice);
+            });
+        }),
+        GameState::Settings => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            text(panel, "Settings", 48.0);
+            row(panel, |row| {
+                button(row, "-", MenuButton::VolumeDown, &choice);
+                text(row, format!("Volume {:.0}%", settings.volume * 100.0), 24.0);
+                button(row, "+", MenuButton::VolumeUp, &choice);
+            });
+            button(panel, format!("Sound: {}", if settings.muted { "off" } else { "on" }), MenuButton::Mute, &choice);
+            button(panel, format!("Layout: {:?}", settings.handedness), MenuButton::Handedness, &choice);
+            button(panel, "Back", MenuButton::MainMenu, &choice);
+        }),
+        GameState::Leaderboards => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            text(panel, "Leaderboards", 48.0);
+            row(panel, |row| {
+                for variant in Variant::iter() {
+                    button(row, variant.to_string(), MenuButton::Variant(variant), &choice);
+                }
+            });
+            row(panel, |row| {
+                house_rule_buttons(row, &choice);
+                for category in Category::iter() {
+                    button(row, category.to_string(), MenuButton::Category(category), &choice);
+                }
+            });
+            let tables = leaderboards.modes.get(&Mode::new(choice.variant, choice.house_rules));
+            match tables.map(|tables| tables.table(choice.category)).filter(|table| !table.is_empty()) {
+                Some(table) => spawn_table(panel, table),
+                None => text(panel, "No games won yet", 24.0),
+            }
+            button(panel, "Back", MenuButton::MainMenu, &choice);
+        }),
+        GameState::Achievements => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            text(panel, "Achievements", 48.0);
+            let unlocked = achievements.0.iter().filter(|achievement| progress.unlocked.contains_key(&achievement.name)).count();
+            text(panel, format!("{unlocked} of {} unlocked", achievements.0.len()), 24.0);
+            for achievement in &achievements.0 {
+                let status = match (progress.unlocked.get(&achievement.name), &achievement.goal) {
+                    (Some(date), _) => format!("unlocked {date}"),
+                    (None, &Goal::Reach(counter, target)) => format!("{} of {target}", progress.count(counter).min(target)),
+                    (None, Goal::Win(_)) => "locked".to_string(),
+                };
+                text(panel, format!("{}: {} ({status})", achievement.name, achievement.description), 22.0);
+            }
+            button(panel, "Back", MenuButton::MainMenu, &choice);
+        }),
+        GameState::Stats => spawn_panel(&mut commands, BACKGROUND, |panel| {
+            text(panel, "Stats", 48.0);
+            for variant in Variant::iter() {
+                let record = stats.variants.get(&variant).copied().unwrap_or_default();
+                text(panel, format!("{variant}: won {} of {}", record.won, record.played), 24.0);
+            }
+            button(panel, "Back", MenuButton::MainMenu, &choice);
+        }),
+    }
+}
+
+fn house_rule_buttons(row: &mut ChildBuilder, choice: &Choice) {
+    let on_off = |on| if on { "on" } else { "off" };
+    button(row, format!("Jokers: {}", on_off(choice.house_rules.jokers)), MenuButton::Jokers, choice);
+    if choice.variant.allows_draw_three() {
+        button(row, format!("Draw three: {}", on_off(choice.house_rules.draw_three)), MenuButton::DrawThree, choice);
+    }
+}
+
+fn spawn_panel(commands: &mut Commands, background: Color, contents: impl FnOnce(&mut ChildBuilder)) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(12.0),
+                ..default()
+            },
+            background_color: background.into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+        Screen,
+    )).with_children(contents);
+}
+
+/// A strip along the bottom of the window that leaves the cards in view.
+fn spawn_bar(commands: &mut Commands, contents: impl FnOnce(&mut ChildBuilder)) {
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                bottom: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                flex_direction: FlexDirection::Row,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(8.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: BACKGROUND.into(),
+            z_index: ZIndex::Global(10),
+            ..default()
+        },
+        Screen,
+    )).with_children(contents);
+}
+
+/// A month of days, a week to a row starting on Monday. Days won are gold and days lost are red.
+fn spawn_calendar(parent: &mut ChildBuilder, calendar: Calendar, today: Date, results: &DailyResults) {
+    const CELL: f32 = 56.0;
+    let cell = |colour: Color| NodeBundle {
+        style: Style {
+            width: Val::Px(CELL),
+            height: Val::Px(CELL),
+            flex_direction: FlexDirection::Column,
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::Center,
+            border: UiRect::all(Val::Px(2.0)),
+            ..default()
+        },
+        background_color: colour.into(),
+        ..default()
+    };
+
+    parent.spawn(NodeBundle {
+        style: Style {
+            display: Display::Grid,
+            grid_template_columns: RepeatedGridTrack::px(7, CELL),
+            column_gap: Val::Px(4.0),
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        ..default()
+    }).with_children(|grid| {
+        for weekday in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"] {
+            grid.spawn(cell(Color::NONE)).with_children(|cell| text(cell, weekday, 18.0));
+        }
+        for _ in 0..calendar.first().weekday() {
+            grid.spawn(cell(Color::NONE));
+        }
+        for date in calendar.days() {
+            let result = results.days.get(&date);
+            let colour = match result {
+                Some(result) if result.won => BUTTON_SELECTED,
+                Some(_) => Color::rgb(0.5, 0.15, 0.15),
+                None => BUTTON,
+            };
+            let mut day = grid.spawn(cell(colour));
+            if date == today {
+                day.insert(BorderColor(Color::WHITE));
+            }
+            day.with_children(|cell| {
+                text(cell, date.day.to_string(), 20.0);
+                if let Some(result) = result.filter(|result| result.won) {
+                    let seconds = result.seconds as u32;
+                    text(cell, format!("{}:{:02}", seconds / 60, seconds % 60), 14.0);
+                }
+            });
+        }
+    });
+}
+
+/// A place to a row, with who won it, their score, time and moves and when.
+fn spawn_table(parent: &mut ChildBuilder, table: &[Entry]) {
+    parent.spawn(NodeBundle {
+        style: Style {
+            display: Display::Grid,
+            grid_template_columns: RepeatedGridTrack::auto(6),
+            column_gap: Val::Px(24.0),
+            row_gap: Val::Px(4.0),
+            ..default()
+        },
+        ..default()
+    }).with_children(|grid| {
+        for heading in ["", "Name", "Score", "Time", "Moves", "Date"] {
+            text(grid, heading, 20.0);
+        }
+        for (place, entry) in table.iter().enumerate() {
+            text(grid, format!("{}.", place + 1), 22.0);
+            text(grid, entry.name.clone(), 22.0);
+            text(grid, entry.points.to_string(), 22.0);
+            text(grid, Score::time(entry.seconds), 22.0);
+            text(grid, entry.moves.to_string(), 22.0);
+            text(grid, entry.date.to_string(), 22.0);
+        }
+    });
+}
+
+fn row(parent: &mut ChildBuilder, contents: impl FnOnce(&mut ChildBuilder)) {
+    parent.spawn(NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Row,
+            flex_wrap: FlexWrap::Wrap,
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            column_gap: Val::Px(8.0),
+            row_gap: Val::Px(8.0),
+            max_width: Val::Percent(90.0),
+            ..default()
+        },
+        ..default()
+    }).with_children(contents);
+}
+
+fn text(parent: &mut ChildBuilder, label: impl Into<String>, size: f32) {
+    parent.spawn(TextBundle::from_section(label, TextStyle { font_size: size, color: Color::WHITE, ..default() }));
+}
+
+fn button(parent: &mut ChildBuilder, label: impl Into<String>, action: MenuButton, choice: &Choice) {
+    parent.spawn((
+        ButtonBundle {
+            style: Style {
+                padding: UiRect::axes(Val::Px(16.0), Val::Px(8.0)),
+                ..default()
+            },
+            background_color: button_colour(action, choice).into(),
+            ..default()
+        },
+        action,
+    )).with_children(|button| text(button, label, 24.0));
+}
+
+/// The cards are hidden while paused, so pausing can't be used to think over a move.
+fn hide_board(state: Res<State<GameState>>, mut q_board: Query<&mut Visibility, With<Board>>) {
+    if !state.is_changed() {
+        return;
+    }
+    let visibility = if *state.get() == GameState::Paused { Visibility::Hidden } else { Visibility::Inherited };
+    for mut board_visibility in q_board.iter_mut() {
+        board_visibility.set_if_neq(visibility);
+    }
+}
@@ -0,0 +1,532 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+use arboard::Clipboard;
+use bevy::prelude::*;
+use strum::IntoEnumIterator;
+
+use super::abandon::Abandon;
+use super::foundation::Foundation;
+use super::layout::Place;
+use super::peaks::PeakSlot;
+use super::replay::{Address, Addresses, Spot};
+use super::rules::Rules;
+use super::settings::{read, write};
+use super::systems::{Activate, MoveRequest};
+use super::table::{PileCards, Table, Tables};
+use super::variant::Variant;
+use super::{Card, CardFace, CardSuit, JokerColour};
+
+/// Where positions are saved and loaded from with Ctrl+S and Ctrl+O.
+const POSITION: &str = "position.txt";
+
+const RANKS: [char; 13] = ['A', '2', '3', '4', '5', '6', '7', '8', '9', 'T', 'J', 'Q', 'K'];
+
+pub struct NotationPlugin;
+
+impl Plugin for NotationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Loaded>()
+            .add_event::<LoadPosition>()
+            .add_systems(Update, load_position);
+    }
+}
+
+/// Cards are written as a rank and a suit, `QH`, `TS` or `7c`, in either case.
+/// Jokers are `XR` and `XB`, followed by `=` and the card they stand in for once resolved.
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Card::Suited { suit, face } => write!(f, "{}{}", RANKS[face.0 as usize - 1], suit_letter(suit)),
+            Card::Joker { colour, stands_for } => {
+                write!(f, "X{}", if colour == JokerColour::Red { 'R' } else { 'B' })?;
+                match stands_for {
+                    Some((suit, face)) => write!(f, "={}", Card::Suited { suit, face }),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for Card {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let code = text.to_ascii_uppercase();
+        if let Some(joker) = code.strip_prefix('X') {
+            let (colour, stands_for) = match joker.split_once('=') {
+                Some((colour, card)) => match card.parse()? {
+                    Card::Suited { suit, face } => (colour, Some((suit, face))),
+                    Card::Joker { .. } => return Err(format!("a joker can't stand in for a joker in `{text}`")),
+                },
+                None => (joker, None),
+            };
+            let colour = match colour {
+                "R" => JokerColour::Red,
+                "B" => JokerColour::Black,
+                _ => return Err(format!("unknown joker `{text}`")),
+            };
+            return Ok(Card::Joker { colour, stands_for });
+        }
+
+        let mut chars = code.chars();
+        let suit = match chars.next_back() {
+            Some('H') => CardSuit::Hearts,
+            Some('D') => CardSuit::Diamonds,
+            Some('C') => CardSuit::Clubs,
+            Some('S') => CardSuit::Spades,
+            _ => return Err(format!("unknown suit in `{text}`")),
+        };
+        let face = match chars.as_str() {
+            "10" => 10,
+            rank => match rank.chars().collect::<Vec<_>>()[..] {
+                [rank] => RANKS.iter().position(|&r| r == rank).ok_or_else(|| format!("unknown rank in `{text}`"))? as u8 + 1,
+                _ => return Err(format!("unknown rank in `{text}`")),
+            },
+        };
+        Ok(Card::Suited { suit, face: CardFace(face) })
+    }
+}
+
+fn suit_letter(suit: CardSuit) -> char {
+    match suit {
+        CardSuit::Hearts => 'H',
+        CardSuit::Diamonds => 'D',
+        CardSuit::Clubs => 'C',
+        CardSuit::Spades => 'S',
+    }
+}
+
+fn cards(text: &str) -> Result<Vec<Card>, String> {
+    text.split_whitespace().map(str::parse).collect()
+}
+
+fn write_cards(f: &mut fmt::Formatter, cards: &[Card]) -> fmt::Result {
+    for card in cards {
+        write!(f, " {card}")?;
+    }
+    Ok(())
+}
+
+/// A move as a player would write it: `s` to turn over or deal from the stock, or `from->to`.
+/// A place on its own means its top card, like `w->t5`, or `t3:2->t5` picks up from the
+/// third card of the pile. `->f` plays the card to whichever foundation will take it.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Move {
+    Stock,
+    Play { from: Address, to: Option<Address> },
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Move::Stock => write!(f, "s"),
+            Move::Play { from, to: Some(to) } => write!(f, "{from}->{to}"),
+            Move::Play { from, to: None } => write!(f, "{from}->f"),
+        }
+    }
+}
+
+impl FromStr for Move {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        if text == "s" {
+            return Ok(Move::Stock);
+        }
+        let (from, to) = text.split_once("->").ok_or_else(|| format!("`{text}` isn't a move"))?;
+        let to = match to {
+            "f" => None,
+            to => Some(to.parse()?),
+        };
+        Ok(Move::Play { from: from.parse()?, to })
+    }
+}
+
+/// A whole table written out as text, one line for each place, with the moves to make from it.
+///
+/// ```text
+/// variant Klondike
+/// stock: 4H 5C 9S
+/// waste: 9D
+/// f1 AH: AH 2H
+/// t1: QH 3C | 7D 6S
+/// moves: t1->f w->t1 s
+/// ```
+///
+/// Stacks are listed from the bottom card up. Tableau cards before the `|` are face down.
+/// Each foundation names the card it is built up from. TriPeaks lists its cards as
+/// `peaks: 1=QH 2=7C` by their place in the deal, counting from the top of the first peak.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Position {
+    pub variant: Variant,
+    pub table: Table,
+    pub moves: Vec<Move>,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let table = &self.table;
+        writeln!(f, "variant {}", self.variant)?;
+        if let Some(stock) = &table.stock {
+            write!(f, "stock:")?;
+            write_cards(f, stock)?;
+            writeln!(f)?;
+        }
+        if let Some(waste) = &table.waste {
+            write!(f, "waste:")?;
+            write_cards(f, waste)?;
+            writeln!(f)?;
+        }
+        for (i, (foundation, _, cards)) in table.foundations.iter().enumerate() {
+            write!(f, "f{} {}:", i + 1, Card::Suited { suit: foundation.suit, face: foundation.base })?;
+            write_cards(f, cards)?;
+            writeln!(f)?;
+        }
+        for (i, pile) in table.piles.iter().enumerate() {
+            write!(f, "t{}:", i + 1)?;
+            write_cards(f, &pile.cards[..pile.face_down])?;
+            if pile.face_down > 0 {
+                write!(f, " |")?;
+            }
+            write_cards(f, &pile.cards[pile.face_down..])?;
+            writeln!(f)?;
+        }
+        if let Some(reserve) = &table.reserve {
+            write!(f, "reserve:")?;
+            write_cards(f, reserve)?;
+            writeln!(f)?;
+        }
+        if let Some(peaks) = &table.peaks {
+            write!(f, "peaks:")?;
+            for (slot, card) in peaks {
+                write!(f, " {}={card}", slot.number() + 1)?;
+            }
+            writeln!(f)?;
+        }
+        if !self.moves.is_empty() {
+            write!(f, "moves:")?;
+            for played in &self.moves {
+                write!(f, " {played}")?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Position {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')).collect();
+        let variant = lines.iter()
+            .find_map(|line| line.strip_prefix("variant "))
+            .ok_or("missing `variant`")?;
+        let variant: Variant = variant.trim().parse().map_err(|_| format!("unknown variant `{variant}`"))?;
+
+        let mut position = Position { variant, ..default() };
+        let mut foundations = vec![];
+        let mut piles = vec![];
+        for line in lines.into_iter().filter(|line| !line.starts_with("variant ")) {
+            let (key, value) = line.split_once(':').ok_or_else(|| format!("missing `:` in `{line}`"))?;
+            let number = |name: &str| match name[1..].parse::<usize>() {
+                Ok(n) if n > 0 => Ok(n - 1),
+                _ => Err(format!("bad number in `{line}`")),
+            };
+            match key.split_whitespace().collect::<Vec<_>>()[..] {
+                ["stock"] => position.table.stock = Some(cards(value)?),
+                ["waste"] => position.table.waste = Some(cards(value)?),
+                ["reserve"] => position.table.reserve = Some(cards(value)?),
+                ["peaks"] => position.table.peaks = Some(value.split_whitespace()
+                    .map(|entry| {
+                        let (number, card) = entry.split_once('=').ok_or_else(|| format!("missing `=` in `{entry}`"))?;
+                        let slot = number.parse::<usize>().ok()
+                            .and_then(|number| PeakSlot::numbered(number.checked_sub(1)?))
+                            .ok_or_else(|| format!("no peak card {number}"))?;
+                        Ok((slot, card.parse()?))
+                    })
+                    .collect::<Result<_, String>>()?),
+                ["moves"] => position.moves = value.split_whitespace().map(str::parse).collect::<Result<_, _>>()?,
+                [pile] if pile.starts_with('t') => {
+                    let (face_down, face_up) = value.split_once('|').unwrap_or(("", value));
+                    let face_down = cards(face_down)?;
+                    let index = number(pile)?;
+                    piles.push((index, PileCards {
+                        column: variant.pile_column(index),
+                        face_down: face_down.len(),
+                        cards: face_down.into_iter().chain(cards(face_up)?).collect(),
+                    }));
+                }
+                [foundation, base] if foundation.starts_with('f') => {
+                    let Card::Suited { suit, face } = base.parse()? else {
+                        return Err(format!("a foundation can't be built from a joker in `{line}`"));
+                    };
+                    let index = number(foundation)?;
+                    let place = Place::Top((variant.foundation_column() + index) as f32);
+                    foundations.push((index, (Foundation { suit, base: face }, place, cards(value)?)));
+                }
+                _ => return Err(format!("unknown line `{line}`")),
+            }
+        }
+        foundations.sort_by_key(|&(index, _)| index);
+        piles.sort_by_key(|&(index, _)| index);
+        if let Some(pair) = foundations.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(format!("`f{}` is listed twice", pair[0].0 + 1));
+        }
+        if let Some(pair) = piles.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+            return Err(format!("`t{}` is listed twice", pair[0].0 + 1));
+        }
+        position.table.foundations = foundations.into_iter().map(|(_, foundation)| foundation).collect();
+        position.table.piles = piles.into_iter().map(|(_, pile)| pile).collect();
+        check_deck(&position)?;
+        Ok(position)
+    }
+}
+
+/// Every card of the variant's decks has to be on the table once for each deck.
+fn check_deck(position: &Position) -> Result<(), String> {
+    let table = &position.table;
+    let mut slots: Vec<usize> = table.peaks.iter().flatten().map(|(slot, _)| slot.number()).collect();
+    slots.sort();
+    if let Some(pair) = slots.windows(2).find(|pair| pair[0] == pair[1]) {
+        return Err(format!("peak card {} is listed twice", pair[0] + 1));
+    }
+
+    let mut counts = BTreeMap::new();
+    let cards = [&table.stock, &table.waste, &table.reserve].into_iter().flatten().flatten()
+        .chain(table.foundations.iter().flat_map(|(_, _, cards)| cards))
+        .chain(table.piles.iter().flat_map(|pile| &pile.cards))
+        .chain(table.peaks.iter().flatten().map(|(_, card)| card));
+    for card in cards {
+        // A joker is the same card whatever it stands in for.
+        let card = match *card {
+            Card::Joker { colour, .. } => Card::Joker { colour, stands_for: None },
+            card => card,
+        };
+        *counts.entry(card.to_string()).or_insert(0) += 1;
+    }
+
+    let decks = position.variant.decks();
+    for (card, &count) in &counts {
+        let most = if card.starts_with('X') { 1 } else { decks };
+        if count > most {
+            return Err(format!("`{card}` is in the position {count} times"));
+        }
+    }
+    for suit in CardSuit::iter() {
+        for face in 1..14 {
+            let card = Card::Suited { suit, face: CardFace(face) }.to_string();
+            if counts.get(&card).is_none_or(|&count| count < decks) {
+                return Err(format!("`{card}` is missing"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A position read in, waiting for the current game to be given up, and the moves still to make from it.
+#[derive(Resource, Default)]
+pub struct Loaded {
+    pub table: Table,
+    moves: VecDeque<Move>,
+}
+
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub enum LoadPosition {
+    File,
+    Clipboard,
+}
+
+/// Ctrl+C copies the position to the clipboard and Ctrl+S saves it, Ctrl+V and Ctrl+O load one.
+pub fn position_keys(
+    keys: Res<ButtonInput<KeyCode>>,
+    variant: Res<Variant>,
+    tables: Tables,
+    mut load_position_event: EventWriter<LoadPosition>,
+) {
+    if !keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]) {
+        return;
+    }
+    if keys.any_just_pressed([KeyCode::KeyC, KeyCode::KeyS]) {
+        let text = Position { variant: *variant, table: tables.capture(), moves: vec![] }.to_string();
+        if keys.just_pressed(KeyCode::KeyC) {
+            if let Err(error) = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(text)) {
+                warn!("Couldn't copy the position: {error}");
+            }
+        } else {
+            write(POSITION, &text);
+        }
+    }
+    if keys.just_pressed(KeyCode::KeyV) {
+        load_position_event.send(LoadPosition::Clipboard);
+    }
+    if keys.just_pressed(KeyCode::KeyO) {
+        load_position_event.send(LoadPosition::File);
+    }
+}
+
+fn load_position(
+    mut load_position_event: EventReader<LoadPosition>,
+    mut loaded: ResMut<Loaded>,
+    mut abandon_event: EventWriter<Abandon>,
+) {
+    let Some(&source) = load_position_event.read().last() else {
+        return;
+    };
+    let text = match source {
+        LoadPosition::File => read(POSITION).ok_or_else(|| format!("there's no {POSITION}")),
+        LoadPosition::Clipboard => Clipboard::new().and_then(|mut clipboard| clipboard.get_text()).map_err(|error| error.to_string()),
+    };
+    match text.and_then(|text| text.parse::<Position>()) {
+        Ok(position) => {
+            *loaded = Loaded { table: position.table, moves: position.moves.into() };
+            abandon_event.send(Abandon::Load { variant: position.variant });
+        }
+        Err(error) => warn!("Couldn't load the position: {error}"),
+    }
+}
+
+/// Makes the moves that came with a loaded position, one a frame like a replay.
+pub fn play_moves(
+    mut loaded: ResMut<Loaded>,
+    addresses: Addresses,
+    rules: Rules,
+    mut activate_event: EventWriter<Activate>,
+    mut move_request_event: EventWriter<MoveRequest>,
+) {
+    let Some(next) = loaded.moves.pop_front() else {
+        return;
+    };
+    let Move::Play { from, to } = next else {
+        if let Some(stock) = addresses.top(Spot::Stock) {
+            activate_event.send(Activate(stock));
+        }
+        return;
+    };
+
+    let card = match from.depth {
+        Some(_) => addresses.entity(from),
+        None => addresses.top(from.spot),
+    };
+    let Some(card) = card else {
+        warn!("Skipping `{next}`, there's no card there");
+        return;
+    };
+    let target = match to {
+        Some(to) => match to.depth {
+            Some(_) => addresses.entity(to),
+            None => addresses.top(to.spot),
+        },
+        None => {
+            let foundations: Vec<Entity> = (0..).map_while(|i| addresses.top(Spot::Foundation(i))).collect();
+            foundations.iter().copied()
+                .find(|&foundation| rules.destination(card, foundation).is_some())
+                .or(foundations.first().copied())
+        }
+    };
+    let Some(target) = target else {
+        warn!("Skipping `{next}`, there's nowhere to put it");
+        return;
+    };
+
+    if rules.variant.plays_to_waste() && to.is_some_and(|to| to.spot == Spot::Waste) {
+        activate_event.send(Activate(card));
+    } else {
+        move_request_event.send(MoveRequest { card, target });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deck() -> Vec<String> {
+        CardSuit::iter()
+            .flat_map(|suit| (1..14).map(move |face| Card::Suited { suit, face: CardFace(face) }.to_string()))
+            .collect()
+    }
+
+    fn klondike(extra: &str) -> String {
+        let deck = deck();
+        format!(
+            "variant Klondike\nstock: {}\nwaste: {}\nf1 AH:\nt1: {} | {}\nt2: {}\n{extra}",
+            deck[10..].join(" "), deck[8..10].join(" "), deck[..3].join(" "), deck[3..5].join(" "), deck[5..8].join(" ")
+        )
+    }
+
+    #[test]
+    fn cards_round_trip() {
+        for suit in CardSuit::iter() {
+            for face in 1..14 {
+                let card = Card::Suited { suit, face: CardFace(face) };
+                assert_eq!(card.to_string().parse::<Card>(), Ok(card));
+            }
+        }
+        for text in ["XR", "XB", "XR=7H", "XB=KS"] {
+            assert_eq!(text.parse::<Card>().map(|card| card.to_string()).as_deref(), Ok(text));
+        }
+    }
+
+    #[test]
+    fn cards_are_read_in_either_case() {
+        let seven = Card::Suited { suit: CardSuit::Clubs, face: CardFace(7) };
+        assert_eq!("7c".parse::<Card>(), Ok(seven));
+        assert_eq!("10h".parse::<Card>(), Ok(Card::Suited { suit: CardSuit::Hearts, face: CardFace(10) }));
+        assert_eq!("xb=7c".parse::<Card>(), Ok(Card::Joker { colour: JokerColour::Black, stands_for: Some((CardSuit::Clubs, CardFace(7))) }));
+    }
+
+    #[test]
+    fn malformed_cards_are_rejected() {
+        for text in ["", "H", "1H", "0S", "77H", "7X", "XG", "XR=XB", "XR=", "X"] {
+            assert!(text.parse::<Card>().is_err(), "`{text}` parsed");
+        }
+    }
+
+    #[test]
+    fn moves_round_trip() {
+        for text in ["s", "w->t1", "t3:2->t5", "t1->f", "r->f2", "p4->w"] {
+            assert_eq!(text.parse::<Move>().map(|played| played.to_string()).as_deref(), Ok(text));
+        }
+    }
+
+    #[test]
+    fn malformed_moves_are_rejected() {
+        for text in ["", "t1", "t1->", "->t1", "t0->f", "q->t1", "t1:x->f", "S"] {
+            assert!(text.parse::<Move>().is_err(), "`{text}` parsed");
+        }
+    }
+
+    #[test]
+    fn positions_round_trip() {
+        let position: Position = klondike("moves: t2->f w->t1 s").parse().unwrap();
+        assert_eq!(position.table.piles[0].face_down, 3);
+        assert_eq!(position.moves.len(), 3);
+        assert_eq!(position.to_string().parse(), Ok(position));
+    }
+
+    #[test]
+    fn positions_need_a_variant_and_known_lines() {
+        assert!(klondike("").replace("variant Klondike", "").parse::<Position>().is_err());
+        assert!(klondike("").replace("Klondike", "Freecell").parse::<Position>().is_err());
+        assert!(klondike("hand: AS").parse::<Position>().is_err());
+        assert!(klondike("t0: ").parse::<Position>().is_err());
+        assert!(klondike("f2 XR:").parse::<Position>().is_err());
+    }
+
+    #[test]
+    fn places_listed_twice_are_rejected() {
+        assert_eq!(klondike("t1:").parse::<Position>(), Err("`t1` is listed twice".to_string()));
+        assert_eq!(klondike("f1 AS:").parse::<Position>(), Err("`f1` is listed twice".to_string()));
+    }
+
+    #[test]
+    fn positions_need_every_card_once() {
+        assert_eq!(klondike("reserve: 5H").parse::<Position>(), Err("`5H` is in the position 2 times".to_string()));
+        assert_eq!(klondike("").replace(" KS", "").parse::<Position>(), Err("`KS` is missing".to_string()));
+        assert!(klondike("reserve: XR XB=7C").parse::<Position>().is_ok());
+        assert!(klondike("reserve: XR XR=7C").parse::<Position>().is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+
+use super::layout::Layout;
+use super::systems::GameEvent;
+use super::{Board, Card, CardBundle};
+
+/// Parent of the 28 cards dealt into the three overlapping TriPeaks peaks.
+#[derive(Component)]
+pub struct Peaks;
+
+/// Where a card sits in the peaks. Columns are counted in half card widths so that a
+/// card is covered by the cards one row down at `col - 1` and `col + 1`.
+#[derive(Component, Copy, Clone, Debug, PartialEq)]
+pub struct PeakSlot {
+    row: u8,
+    col: u8,
+}
+
+impl PeakSlot {
+    fn covers(&self, other: &PeakSlot) -> bool {
+        self.row == other.row + 1 && self.col.abs_diff(other.col) == 1
+    }
+
+    /// Where the slot comes in the deal, counting from the top of the first peak.
+    pub fn number(&self) -> usize {
+        peak_slots().iter().position(|slot| slot == self).unwrap_or_default()
+    }
+
+    pub fn numbered(number: usize) -> Option<PeakSlot> {
+        peak_slots().get(number).copied()
+    }
+
+    pub fn transform(&self, layout: &Layout) -> Transform {
+        let step = layout.peak_step();
+        Transform::from_xyz(
+            (self.col as f32 - 9.0) * step.x,
+            -step.y * self.row as f32,
+            (self.row * 10 + 1) as f32
+        )
+    }
+}
+
+fn peak_slots() -> Vec<PeakSlot> {
+    let mut slots = vec![];
+    for peak in 0..3 {
+        slots.push(PeakSlot { row: 0, col: peak * 6 + 3 });
+    }
+    for peak in 0..3 {
+        slots.push(PeakSlot { row: 1, col: peak * 6 + 2 });
+        slots.push(PeakSlot { row: 1, col: peak * 6 + 4 });
+    }
+    for col in 0..9 {
+        slots.push(PeakSlot { row: 2, col: col * 2 + 1 });
+    }
+    for col in 0..10 {
+        slots.push(PeakSlot { row: 3, col: col * 2 });
+    }
+    slots
+}
+
+pub fn spawn_peaks(commands: &mut Commands, cards: Vec<Card>, asset_server: &Res<AssetServer>) {
+    spawn_peak_cards(commands, peak_slots().into_iter().zip(cards).collect(), asset_server);
+}
+
+/// Spawns the peaks with each card in the given slot. The cards nothing covers are face up,
+/// as they would be in play, so a position put back by undo doesn't turn them over again.
+pub fn spawn_peak_cards(commands: &mut Commands, cards: Vec<(PeakSlot, Card)>, asset_server: &Res<AssetServer>) {
+    let slots: Vec<PeakSlot> = cards.iter().map(|&(slot, _)| slot).collect();
+    commands.spawn((
+        // Placed in the middle of the tableau row, and its cards in their slots, by the layout.
+        SpatialBundle {
+            transform: Transform::from_xyz(0.0, 0.0, -100.0),
+            ..default()
+        },
+        Peaks,
+        Board
+    ))
+    .with_children(|peaks| {
+        for (slot, card) in cards {
+            let face_up = !slots.iter().any(|other| other.covers(&slot));
+            peaks.spawn((
+                CardBundle {
+                    card,
+                    sprite: SpriteBundle {
+                        texture: if face_up {
+                            asset_server.load(card.texture())
+                        } else {
+                            asset_server.load("cards/Back Blue 1.png")
+                        },
+                        ..default()
+                    },
+                    pickable_bundle: PickableBundle {
+                        pickable: if face_up { Pickable::default() } else { Pickable::IGNORE },
+                        ..default()
+                    }
+                },
+                slot
+            ));
+        }
+    });
+}
+
+/// A peak card can be played once no card is left covering it.
+pub fn is_exposed(card: Entity, peaks: &Children, q_slots: &Query<&PeakSlot>) -> bool {
+    let Ok(slot) = q_slots.get(card) else {
+        return false;
+    };
+    !peaks.iter()
+        .filter_map(|&other| q_slots.get(other).ok())
+        .any(|other| other.covers(slot))
+}
+
+pub fn flip_exposed_peaks(
+    q_peaks: Query<&Children, With<Peaks>>,
+    q_slots: Query<&PeakSlot>,
+    mut q_cards: Query<(&mut Pickable, &mut Handle<Image>, &Card), With<PeakSlot>>,
+    asset_server: Res<AssetServer>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    for peaks in q_peaks.iter() {
+        for &card_entity in peaks {
+            if is_exposed(card_entity, peaks, &q_slots) {
+                if let Ok((mut pickable, mut texture, card)) = q_cards.get_mut(card_entity) {
+                    pickable.set_if_neq(Pickable::default());
+                    if texture.set_if_neq(asset_server.load(card.texture())) {
+                        game_event.send(GameEvent::Flipped);
+                    }
+                }
+            }
+        }
+    }
+}
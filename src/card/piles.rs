@@ -1,6 +1,8 @@
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
 
+use super::layout::{Layout, Place};
+use super::systems::{GameEvent, MoveRequest};
 use super::{Board, Card, CardBundle};
 
 #[derive(Component)]
@@ -9,7 +11,10 @@ pub struct Pile;
 #[derive(Component)]
 pub struct Base;
 
-pub fn spawn_pile(commands: &mut Commands, cards: Vec<Card>, x_position: u32, asset_server: &Res<AssetServer>) {
+/// Deals `cards` into a new pile, bottom card first, with the first `face_down` of them face down.
+/// Each card is a child of the one below it, so a card always carries the cards on top of it.
+pub fn spawn_pile(commands: &mut Commands, cards: Vec<Card>, face_down: usize, column: f32, asset_server: &Res<AssetServer>) {
+    let mut parent = Entity::PLACEHOLDER;
     commands.spawn((
         SpriteBundle {
             sprite: Sprite {
@@ -21,102 +26,169 @@ pub fn spawn_pile(commands: &mut Commands, cards: Vec<Card>, x_position: u32, as
                 },
                 ..default()
             },
-            transform: Transform::from_xyz(-650.0 + (x_position*150) as f32, 100.0, -100.0),
-            texture: asset_server.load(format!("cards/Back Blue 1.png")),
+            transform: Transform::from_xyz(0.0, 0.0, -100.0),
+            texture: asset_server.load("cards/Back Blue 1.png"),
             ..default()
         },
         Base,
+        Place::Tableau(column),
         Board,
-        On::<Pointer<DragStart>>::target_insert(Pickable::IGNORE), // Disable picking
-        On::<Pointer<DragEnd>>::target_insert(Pickable::default()), // Re-enable picking
     )).with_children(|base| {
-        base.spawn((
+        parent = base.spawn((
             SpatialBundle {
                 ..default()
             },
             Pile,
-            On::<Pointer<DragStart>>::target_insert(Pickable::IGNORE), // Disable picking
-            On::<Pointer<DragEnd>>::target_insert(Pickable::default()), // Re-enable picking
-            On::<Pointer<Drag>>::target_component_mut::<Transform>(|drag, transform| {
-                transform.translation.x += drag.delta.x;
-                transform.translation.y -= drag.delta.y;
-                transform.translation.z = 1000.0;
-            })
-        ))
-        .with_children(|pile| {
-            for (i, &card) in cards.iter().enumerate() {
-                if i == cards.len() - 1 {
-                    pile.spawn((
-                        CardBundle {
-                            card,
-                            sprite : SpriteBundle {
-                                transform: Transform::from_xyz(0.0, -((i*50) as f32), (i*10+1) as f32),
-                                texture: asset_server.load(format!("cards/{} {}.png", card.suit.to_string(), card.face.0)),
-                                ..default()
-                            },
-                            pickable_bundle: PickableBundle::default()
-                        },
-                    ));
-                } else {
-                    pile.spawn((
-                        CardBundle {
-                            card,
-                            sprite : SpriteBundle {
-                                transform: Transform::from_xyz(0.0, -((i*50) as f32), (i*10+1) as f32),
-                                texture: asset_server.load("cards/Back Blue 1.png"),
-                                ..default()
-                            },
-                            pickable_bundle: PickableBundle {
-                                pickable: Pickable::IGNORE,
-                                ..default()
-                            }
-                        },
-                    ));
-                }
-            }
-        });
+        )).id();
     });
-    
+
+    for (i, card) in cards.into_iter().enumerate() {
+        let transform = Transform::from_xyz(0.0, 0.0, if i == 0 { 1.0 } else { 10.0 });
+        let child = if i >= face_down {
+            commands.spawn(
+                CardBundle {
+                    card,
+                    sprite : SpriteBundle {
+                        transform,
+                        texture: asset_server.load(card.texture()),
+                        ..default()
+                    },
+                    pickable_bundle: PickableBundle::default()
+                },
+            ).id()
+        } else {
+            commands.spawn(
+                CardBundle {
+                    card,
+                    sprite : SpriteBundle {
+                        transform,
+                        texture: asset_server.load("cards/Back Blue 1.png"),
+                        ..default()
+                    },
+                    pickable_bundle: PickableBundle {
+                        pickable: Pickable::IGNORE,
+                        ..default()
+                    }
+                },
+            ).id()
+        };
+        commands.entity(parent).add_child(child);
+        parent = child;
+    }
 }
 
+/// Fans the cards of each pile down from its base, squeezing piles too long to fit.
 pub fn format_piles(
     q_pile: &Query<&Children, With<Pile>>, 
     q_children: &Query<&Children, With<Card>>,
-    transform_query: &mut Query<&mut Transform, With<Card>>
+    transform_query: &mut Query<&mut Transform, With<Card>>,
+    q_textures: &Query<&Handle<Image>, With<Card>>,
+    back: &Handle<Image>,
+    layout: &Layout
 ) {
     for children in q_pile.iter() {
-        for (i, &child) in children.iter().enumerate() {
-            if let Ok(mut transform) = transform_query.get_mut(child) {
-                transform.translation.x = 0.0;
-                transform.translation.y = -((i*50) as f32);
-                transform.translation.z = (i*10+1) as f32;
-            }
+        for &child in children.iter() {
+            let cards: Vec<Entity> = std::iter::once(child).chain(q_children.iter_descendants(child)).collect();
+            // Whether the card each of the others sits on is face down.
+            let covers_face_down: Vec<bool> = cards.iter()
+                .map(|&card| q_textures.get(card).is_ok_and(|texture| texture == back))
+                .take(cards.len().saturating_sub(1))
+                .collect();
+            let (face_down_fan, face_up_fan) = pile_fans(&covers_face_down, layout);
 
-            for child in q_children.iter_descendants(child) {
-                if let Ok(mut transform) = transform_query.get_mut(child) {
+            for (i, &card) in cards.iter().enumerate() {
+                if let Ok(mut transform) = transform_query.get_mut(card) {
                     transform.translation.x = 0.0;
-                    transform.translation.y = -50.0;
-                    transform.translation.z = 10.0;
+                    if i == 0 {
+                        transform.translation.y = 0.0;
+                        transform.translation.z = 1.0;
+                    } else {
+                        transform.translation.y = -if covers_face_down[i - 1] { face_down_fan } else { face_up_fan };
+                        transform.translation.z = 10.0;
+                    }
                 }
             }
         }
     }
 }
 
+/// How far down from a face-down and a face-up card the next card goes.
+fn pile_fans(covers_face_down: &[bool], layout: &Layout) -> (f32, f32) {
+    let face_down = covers_face_down.iter().filter(|&&face_down| face_down).count() as f32;
+    let face_up = covers_face_down.len() as f32 - face_down;
+    let depth = layout.tableau_depth();
+
+    let face_down_fan = if face_down == 0.0 {
+        layout.fan_face_down()
+    } else {
+        ((depth - face_up * layout.min_fan()) / face_down).clamp(layout.min_fan_face_down(), layout.fan_face_down())
+    };
+    let face_up_fan = if face_up == 0.0 {
+        layout.fan()
+    } else {
+        ((depth - face_down * face_down_fan) / face_up).clamp(layout.min_fan(), layout.fan())
+    };
+    (face_down_fan, face_up_fan)
+}
+
 pub fn flip_last_card_of_piles(
-    mut drag_drop_event: EventReader<Pointer<Drop>>,
-    q_piles: Query<&Children, With<Pile>>,
+    mut move_request_event: EventReader<MoveRequest>,
+    q_piles: Query<Entity, With<Pile>>,
+    q_children: Query<&Children>,
     mut q_pickable: Query<(Entity, &mut Pickable, &mut Handle<Image>, &Card)>,
-    asset_server: Res<AssetServer>
+    asset_server: Res<AssetServer>,
+    mut game_event: EventWriter<GameEvent>,
 ) {
-    for _drop in drag_drop_event.read() {
-        for piles in q_piles.iter() {
-            if let Some(&last_in_pile) = piles.iter().last() {
+    for _request in move_request_event.read() {
+        for pile in q_piles.iter() {
+            if let Some(last_in_pile) = q_children.iter_descendants(pile).last() {
                 if let Ok((_, mut pickable, mut texture, card))= q_pickable.get_mut(last_in_pile) {
                     pickable.set_if_neq(Pickable::default());
-                    texture.set_if_neq(asset_server.load(format!("cards/{} {}.png", card.suit.to_string(), card.face.0)));
+                    if texture.set_if_neq(asset_server.load(card.texture())) {
+                        game_event.send(GameEvent::Flipped);
+                    }
                 }
             }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_piles_are_fanned_fully() {
+        let layout = Layout::default();
+        assert_eq!(pile_fans(&[], &layout), (layout.fan_face_down(), layout.fan()));
+        assert_eq!(pile_fans(&[true, true, false], &layout), (layout.fan_face_down(), layout.fan()));
+    }
+
+    #[test]
+    fn long_piles_are_squeezed_onto_the_table() {
+        let layout = Layout::default();
+        let covers_face_down: Vec<bool> = (0..12).map(|i| i < 4).collect();
+        let (face_down_fan, face_up_fan) = pile_fans(&covers_face_down, &layout);
+        assert_eq!(face_down_fan, layout.fan_face_down());
+        assert!(face_up_fan < layout.fan() && face_up_fan > layout.min_fan());
+        assert!(4.0 * face_down_fan + 8.0 * face_up_fan <= layout.tableau_depth() + 0.01);
+    }
+
+    #[test]
+    fn face_down_cards_give_way_once_face_up_cards_cant() {
+        let layout = Layout::default();
+        let covers_face_down: Vec<bool> = (0..20).map(|i| i < 10).collect();
+        let (face_down_fan, face_up_fan) = pile_fans(&covers_face_down, &layout);
+        assert!(face_down_fan < layout.fan_face_down() && face_down_fan > layout.min_fan_face_down());
+        assert!((face_up_fan - layout.min_fan()).abs() < 0.01);
+        assert!(10.0 * face_down_fan + 10.0 * face_up_fan <= layout.tableau_depth() + 0.01);
+    }
+
+    #[test]
+    fn piles_too_long_to_fit_stop_at_the_closest_fans() {
+        let layout = Layout::default();
+        let (face_down_fan, face_up_fan) = pile_fans(&[false; 200], &layout);
+        assert_eq!(face_down_fan, layout.fan_face_down());
+        assert_eq!(face_up_fan, layout.min_fan());
+    }
+}
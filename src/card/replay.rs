@@ -0,0 +1,594 @@
+use std::fmt;
+use std::str::FromStr;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use super::foundation::Foundation;
+use super::history::{Redo, Undo};
+use super::layout::Place;
+use super::peaks::{PeakSlot, Peaks};
+use super::piles::Base;
+use super::reserve::Reserve;
+use super::settings::{read, write};
+use super::stock::Stock;
+use super::systems::{Activate, MoveRequest};
+use super::variant::{HouseRules, Variant};
+use super::waste::Waste;
+use super::{Board, Card, GameState, NewGame, Seed};
+
+/// Where the most recent game is recorded, so that it can be watched again or shared.
+pub const LAST_GAME: &str = "last.replay";
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Recorder>()
+            .init_resource::<Replay>()
+            .add_event::<ReplayControl>()
+            .add_systems(OnEnter(GameState::Replay), start_replay)
+            .add_systems(OnExit(GameState::Replay), clear_replay)
+            .add_systems(Update, (
+                start_recording,
+                replay_keys.run_if(in_state(GameState::Replay)),
+                show_progress,
+            ));
+    }
+}
+
+/// A pile or other place on the table, numbered left to right from 0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Spot {
+    Stock,
+    Waste,
+    Foundation(usize),
+    Tableau(usize),
+    Reserve,
+    /// A card in the TriPeaks peaks, by its `PeakSlot::number`.
+    Peak(usize),
+}
+
+/// A card, or the empty spot itself, in a way that stays the same when the table is dealt again.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Address {
+    pub spot: Spot,
+    /// How many cards up from the bottom of the spot, or `None` for the spot itself.
+    pub depth: Option<usize>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Action {
+    Activate(Address),
+    Move { card: Address, target: Address },
+    Undo,
+    Redo,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Step {
+    /// Seconds of play since the deal.
+    pub at: f32,
+    pub action: Action,
+}
+
+/// A whole game: the deal and everything the player did, which is enough to play it again.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Recording {
+    pub variant: Variant,
+    pub jokers: bool,
+    pub draw_three: bool,
+    pub seed: u64,
+    pub steps: Vec<Step>,
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.spot {
+            Spot::Stock => write!(f, "s")?,
+            Spot::Waste => write!(f, "w")?,
+            Spot::Foundation(i) => write!(f, "f{}", i + 1)?,
+            Spot::Tableau(i) => write!(f, "t{}", i + 1)?,
+            Spot::Reserve => write!(f, "r")?,
+            Spot::Peak(i) => write!(f, "p{}", i + 1)?,
+        }
+        match self.depth {
+            Some(depth) => write!(f, ":{depth}"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for Address {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let (spot, depth) = match text.split_once(':') {
+            Some((spot, depth)) => (spot, Some(depth.parse().map_err(|_| format!("bad depth in `{text}`"))?)),
+            None => (text, None),
+        };
+        let number = || match spot[1..].parse::<usize>() {
+            Ok(n) if n > 0 => Ok(n - 1),
+            _ => Err(format!("bad number in `{text}`")),
+        };
+        let spot = match spot.chars().next() {
+            Some('s') if spot.len() == 1 => Spot::Stock,
+            Some('w') if spot.len() == 1 => Spot::Waste,
+            Some('r') if spot.len() == 1 => Spot::Reserve,
+            Some('f') => Spot::Foundation(number()?),
+            Some('t') => Spot::Tableau(number()?),
+            Some('p') => Spot::Peak(number()?),
+            _ => return Err(format!("unknown place `{text}`")),
+        };
+        Ok(Address { spot, depth })
+    }
+}
+
+/// One step per line: the time, then `u` or `r` for undo and redo, a single address for
+/// a click, or `card>target` for a move, e.g. `12.40 t3:4>f2`.
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:.2} ", self.at)?;
+        match self.action {
+            Action::Activate(address) => write!(f, "{address}"),
+            Action::Move { card, target } => write!(f, "{card}>{target}"),
+            Action::Undo => write!(f, "u"),
+            Action::Redo => write!(f, "r"),
+        }
+    }
+}
+
+impl FromStr for Step {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, String> {
+        let (at, action) = line.split_once(' ').ok_or_else(|| format!("missing move in `{line}`"))?;
+        let at = at.parse().map_err(|_| format!("bad time in `{line}`"))?;
+        let action = match action.trim() {
+            "u" => Action::Undo,
+            "r" => Action::Redo,
+            action => match action.split_once('>') {
+                Some((card, target)) => Action::Move { card: card.parse()?, target: target.parse()? },
+                None => Action::Activate(action.parse()?),
+            },
+        };
+        Ok(Step { at, action })
+    }
+}
+
+impl fmt::Display for Recording {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "variant {}", self.variant)?;
+        writeln!(f, "jokers {}", self.jokers)?;
+        writeln!(f, "draw {}", if self.draw_three { 3 } else { 1 })?;
+        writeln!(f, "seed {}", self.seed)?;
+        for step in &self.steps {
+            writeln!(f, "{step}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Recording {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, String> {
+        let mut recording = Recording::default();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+            match line.split_once(' ') {
+                Some(("variant", name)) => recording.variant = name.parse().map_err(|_| format!("unknown variant `{name}`"))?,
+                Some(("jokers", jokers)) => recording.jokers = jokers.parse().map_err(|_| format!("bad jokers `{jokers}`"))?,
+                Some(("draw", "1")) => recording.draw_three = false,
+                Some(("draw", "3")) => recording.draw_three = true,
+                Some(("draw", draw)) => return Err(format!("bad draw `{draw}`")),
+                Some(("seed", seed)) => recording.seed = seed.parse().map_err(|_| format!("bad seed `{seed}`"))?,
+                _ => recording.steps.push(line.parse()?),
+            }
+        }
+        Ok(recording)
+    }
+}
+
+/// Finds the `Address` of a card on the table, and the card at an `Address`.
+#[derive(SystemParam)]
+#[allow(clippy::type_complexity)]
+pub struct Addresses<'w, 's> {
+    q_roots: Query<'w, 's, (Entity, &'static Place, Has<Waste>, Has<Foundation>, Has<Base>, Has<Reserve>)>,
+    q_stock: Query<'w, 's, (Entity, Option<&'static Children>), With<Stock>>,
+    q_peaks: Query<'w, 's, &'static Children, With<Peaks>>,
+    q_slots: Query<'w, 's, &'static PeakSlot>,
+    q_parent: Query<'w, 's, &'static Parent>,
+    q_children: Query<'w, 's, &'static Children>,
+    q_cards: Query<'w, 's, (), With<Card>>,
+    q_transforms: Query<'w, 's, &'static Transform, With<Card>>,
+}
+
+impl Addresses<'_, '_> {
+    /// The places of one kind, left to right.
+    fn roots(&self, spot: Spot) -> Vec<Entity> {
+        let mut roots: Vec<(Entity, f32)> = self.q_roots.iter()
+            .filter(|&(_, _, waste, foundation, base, reserve)| match spot {
+                Spot::Waste => waste,
+                Spot::Foundation(_) => foundation,
+                Spot::Tableau(_) => base,
+                Spot::Reserve => reserve,
+                Spot::Stock | Spot::Peak(_) => false,
+            })
+            .map(|(entity, place, ..)| (entity, place.column()))
+            .collect();
+        roots.sort_by(|a, b| a.1.total_cmp(&b.1));
+        roots.into_iter().map(|(entity, _)| entity).collect()
+    }
+
+    fn cards_of(&self, root: Entity) -> Vec<Entity> {
+        self.q_children.iter_descendants(root).filter(|&entity| self.q_cards.contains(entity)).collect()
+    }
+
+    pub fn address(&self, entity: Entity) -> Option<Address> {
+        let parent = self.q_parent.get(entity).ok().map(Parent::get);
+        if self.q_stock.contains(entity) || parent.is_some_and(|parent| self.q_stock.contains(parent)) {
+            return Some(Address { spot: Spot::Stock, depth: None });
+        }
+        if parent.is_some_and(|parent| self.q_peaks.contains(parent)) {
+            let slot = self.q_slots.get(entity).ok()?;
+            return Some(Address { spot: Spot::Peak(slot.number()), depth: None });
+        }
+
+        let root = self.q_parent.iter_ancestors(entity).last().unwrap_or(entity);
+        let (_, _, waste, foundation, base, reserve) = self.q_roots.get(root).ok()?;
+        let kind = match (waste, foundation, base, reserve) {
+            (true, ..) => Spot::Waste,
+            (_, true, ..) => Spot::Foundation(0),
+            (_, _, true, _) => Spot::Tableau(0),
+            (.., true) => Spot::Reserve,
+            _ => return None,
+        };
+        let index = self.roots(kind).iter().position(|&other| other == root)?;
+        let spot = match kind {
+            Spot::Foundation(_) => Spot::Foundation(index),
+            Spot::Tableau(_) => Spot::Tableau(index),
+            kind => kind,
+        };
+        let depth = if entity == root {
+            None
+        } else {
+            Some(self.cards_of(root).iter().position(|&card| card == entity)?)
+        };
+        Some(Address { spot, depth })
+    }
+
+    /// The top card of `spot`, or the spot itself if it's empty.
+    pub fn top(&self, spot: Spot) -> Option<Entity> {
+        let root = self.entity(Address { spot, depth: None })?;
+        match spot {
+            Spot::Stock | Spot::Peak(_) => Some(root),
+            _ => Some(self.cards_of(root).last().copied().unwrap_or(root)),
+        }
+    }
+
+    pub fn entity(&self, address: Address) -> Option<Entity> {
+        let root = match address.spot {
+            Spot::Stock => {
+                // Clicking the stock lands on its top card, if it has any.
+                let (stock, children) = self.q_stock.get_single().ok()?;
+                let z = |card| self.q_transforms.get(card).map_or(0.0, |transform| transform.translation.z);
+                return Some(children
+                    .and_then(|children| children.iter().copied().max_by(|&a, &b| z(a).total_cmp(&z(b))))
+                    .unwrap_or(stock));
+            }
+            Spot::Peak(number) => {
+                return self.q_peaks.iter().flatten().copied()
+                    .find(|&card| self.q_slots.get(card).is_ok_and(|slot| slot.number() == number));
+            }
+            Spot::Waste | Spot::Reserve => self.roots(address.spot).first().copied()?,
+            Spot::Foundation(index) | Spot::Tableau(index) => self.roots(address.spot).get(index).copied()?,
+        };
+        match address.depth {
+            Some(depth) => self.cards_of(root).get(depth).copied(),
+            None => Some(root),
+        }
+    }
+}
+
+/// The game being played, recorded as it goes. Loaded positions aren't recorded.
+#[derive(Resource, Default)]
+pub struct Recorder {
+    recording: Option<Recording>,
+    clock: f32,
+}
+
+/// The recording being played back, and how far through it is.
+#[derive(Resource, Default)]
+pub struct Replay {
+    recording: Recording,
+    next: usize,
+    clock: f32,
+    speed: f32,
+    pub playing: bool,
+    /// The variant and house rules the player had picked, put back once the replay ends.
+    chosen: Option<(Variant, HouseRules)>,
+}
+
+impl Replay {
+    pub fn progress(&self) -> String {
+        let state = if self.next == self.recording.steps.len() {
+            "finished"
+        } else if self.playing {
+            "playing"
+        } else {
+            "paused"
+        };
+        format!("Move {} of {}, {}x speed, {state}", self.next, self.recording.steps.len(), self.speed)
+    }
+}
+
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub enum ReplayControl {
+    /// Play or pause.
+    Play,
+    /// Play the next move straight away.
+    Step,
+    Slower,
+    Faster,
+}
+
+/// The text showing how far through the replay it is.
+#[derive(Component)]
+pub struct ReplayProgress;
+
+fn start_recording(
+    mut new_game_event: EventReader<NewGame>,
+    mut recorder: ResMut<Recorder>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    seed: Res<Seed>,
+) {
+    let Some(&new_game) = new_game_event.read().last() else {
+        return;
+    };
+    let recording = match new_game {
+        NewGame::Load => None,
+        NewGame::Restart => recorder.recording.take().map(|recording| Recording { steps: vec![], ..recording }),
+        NewGame::Deal => Some(Recording {
+            variant: *variant,
+            jokers: house_rules.jokers,
+            draw_three: house_rules.draw_three,
+            seed: seed.0,
+            steps: vec![],
+        }),
+    };
+    *recorder = Recorder { recording, clock: 0.0 };
+}
+
+/// Records what the player does this frame, before it changes where the cards are.
+#[allow(clippy::too_many_arguments)]
+pub fn record_moves(
+    time: Res<Time>,
+    variant: Res<Variant>,
+    mut recorder: ResMut<Recorder>,
+    mut activate_event: EventReader<Activate>,
+    mut move_request_event: EventReader<MoveRequest>,
+    mut undo_event: EventReader<Undo>,
+    mut redo_event: EventReader<Redo>,
+    addresses: Addresses,
+) {
+    recorder.clock += time.delta_seconds();
+    let mut actions = vec![];
+    for &Activate(entity) in activate_event.read() {
+        // A click on a card that can be dragged is turned into a move, which is recorded instead.
+        match addresses.address(entity) {
+            Some(address) if address.spot == Spot::Stock || !variant.drags() => actions.push(Action::Activate(address)),
+            _ => {}
+        }
+    }
+    for request in move_request_event.read() {
+        if let (Some(card), Some(target)) = (addresses.address(request.card), addresses.address(request.target)) {
+            actions.push(Action::Move { card, target });
+        }
+    }
+    actions.extend(undo_event.read().map(|_| Action::Undo));
+    actions.extend(redo_event.read().map(|_| Action::Redo));
+
+    let at = recorder.clock;
+    if let Some(recording) = recorder.recording.as_mut().filter(|_| !actions.is_empty()) {
+        recording.steps.extend(actions.into_iter().map(|action| Step { at, action }));
+        write(LAST_GAME, &recording.to_string());
+    }
+}
+
+fn start_replay(
+    mut replay: ResMut<Replay>,
+    mut variant: ResMut<Variant>,
+    mut house_rules: ResMut<HouseRules>,
+    mut seed: ResMut<Seed>,
+    mut new_game_event: EventWriter<NewGame>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let recording: Recording = match read(LAST_GAME).ok_or_else(|| "nothing recorded".to_string()).and_then(|text| text.parse()) {
+        Ok(recording) => recording,
+        Err(error) => {
+            warn!("Can't replay {LAST_GAME}: {error}");
+            next_state.set(GameState::MainMenu);
+            return;
+        }
+    };
+    let chosen = Some((*variant, *house_rules));
+    *variant = recording.variant;
+    *house_rules = HouseRules { jokers: recording.jokers, draw_three: recording.draw_three };
+    seed.0 = recording.seed;
+    *replay = Replay { recording, next: 0, clock: 0.0, speed: 1.0, playing: true, chosen };
+    new_game_event.send(NewGame::Deal);
+}
+
+/// The replayed game isn't one the player can carry on with, and the next deal should be of
+/// whatever the player had picked rather than what was recorded.
+fn clear_replay(
+    mut commands: Commands,
+    q_board: Query<Entity, With<Board>>,
+    mut replay: ResMut<Replay>,
+    mut variant: ResMut<Variant>,
+    mut house_rules: ResMut<HouseRules>,
+) {
+    for entity in q_board.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some((chosen_variant, chosen_house_rules)) = replay.chosen.take() {
+        *variant = chosen_variant;
+        *house_rules = chosen_house_rules;
+    }
+}
+
+/// Space plays and pauses, the right arrow steps, and the square brackets change the speed.
+fn replay_keys(keys: Res<ButtonInput<KeyCode>>, mut control_event: EventWriter<ReplayControl>) {
+    if keys.just_pressed(KeyCode::Space) {
+        control_event.send(ReplayControl::Play);
+    }
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        control_event.send(ReplayControl::Step);
+    }
+    if keys.just_pressed(KeyCode::BracketLeft) {
+        control_event.send(ReplayControl::Slower);
+    }
+    if keys.just_pressed(KeyCode::BracketRight) {
+        control_event.send(ReplayControl::Faster);
+    }
+}
+
+/// Sends the recorded moves through the same events as the player's input, one a frame.
+#[allow(clippy::too_many_arguments)]
+pub fn play_replay(
+    time: Res<Time>,
+    mut replay: ResMut<Replay>,
+    mut control_event: EventReader<ReplayControl>,
+    addresses: Addresses,
+    mut activate_event: EventWriter<Activate>,
+    mut move_request_event: EventWriter<MoveRequest>,
+    mut undo_event: EventWriter<Undo>,
+    mut redo_event: EventWriter<Redo>,
+) {
+    let mut step = false;
+    for &control in control_event.read() {
+        match control {
+            ReplayControl::Play => replay.playing = !replay.playing,
+            ReplayControl::Step => step = true,
+            ReplayControl::Slower => replay.speed = (replay.speed / 2.0).max(0.25),
+            ReplayControl::Faster => replay.speed = (replay.speed * 2.0).min(16.0),
+        }
+    }
+    if replay.playing {
+        replay.clock += time.delta_seconds() * replay.speed;
+    }
+
+    let Some(&next) = replay.recording.steps.get(replay.next) else {
+        replay.playing = false;
+        return;
+    };
+    if step {
+        replay.clock = replay.clock.max(next.at);
+    } else if next.at > replay.clock {
+        return;
+    }
+    replay.next += 1;
+
+    match next.action {
+        Action::Activate(address) => {
+            if let Some(entity) = addresses.entity(address) {
+                activate_event.send(Activate(entity));
+                return;
+            }
+        }
+        Action::Move { card, target } => {
+            if let (Some(card), Some(target)) = (addresses.entity(card), addresses.entity(target)) {
+                move_request_event.send(MoveRequest { card, target });
+                return;
+            }
+        }
+        Action::Undo => {
+            undo_event.send(Undo);
+            return;
+        }
+        Action::Redo => {
+            redo_event.send(Redo);
+            return;
+        }
+    }
+    warn!("Skipping `{next}` in the replay, there's no card there");
+}
+
+fn show_progress(replay: Res<Replay>, mut q_progress: Query<&mut Text, With<ReplayProgress>>) {
+    for mut text in q_progress.iter_mut() {
+        if let Some(section) = text.sections.first_mut() {
+            let progress = replay.progress();
+            if section.value != progress {
+                section.value = progress;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_round_trip() {
+        for text in ["s", "w", "r", "f1", "t7", "t3:2", "p28", "f4:0"] {
+            assert_eq!(text.parse::<Address>().map(|address| address.to_string()).as_deref(), Ok(text));
+        }
+    }
+
+    #[test]
+    fn malformed_addresses_are_rejected() {
+        for text in ["", "t", "t0", "tx", "sw", "q1", "t1:", "t1:-1", ":1"] {
+            assert!(text.parse::<Address>().is_err(), "`{text}` parsed");
+        }
+    }
+
+    #[test]
+    fn steps_round_trip() {
+        let steps = [
+            Step { at: 1.5, action: Action::Activate(Address { spot: Spot::Stock, depth: None }) },
+            Step { at: 2.25, action: Action::Move {
+                card: Address { spot: Spot::Tableau(2), depth: Some(1) },
+                target: Address { spot: Spot::Foundation(0), depth: None },
+            } },
+            Step { at: 3.0, action: Action::Undo },
+            Step { at: 4.0, action: Action::Redo },
+        ];
+        for step in steps {
+            assert_eq!(step.to_string().parse(), Ok(step));
+        }
+        assert_eq!("2.25 t3:1>f1".parse::<Step>().map(|step| step.to_string()).as_deref(), Ok("2.25 t3:1>f1"));
+    }
+
+    #[test]
+    fn malformed_steps_are_rejected() {
+        for text in ["", "u", "1.0", "x u", "1.0 ", "1.0 t1>", "1.0 >f1", "1.0 q"] {
+            assert!(text.parse::<Step>().is_err(), "`{text}` parsed");
+        }
+    }
+
+    #[test]
+    fn recordings_round_trip() {
+        let recording = Recording {
+            variant: Variant::Canfield,
+            jokers: true,
+            draw_three: false,
+            seed: 42,
+            steps: vec![
+                Step { at: 0.5, action: Action::Activate(Address { spot: Spot::Stock, depth: None }) },
+                Step { at: 1.0, action: Action::Undo },
+            ],
+        };
+        assert_eq!(recording.to_string().parse(), Ok(recording));
+        let text = "# a comment\nvariant Klondike\n\ndraw 3\nseed 7\n";
+        assert_eq!(text.parse(), Ok(Recording { variant: Variant::Klondike, draw_three: true, seed: 7, ..default() }));
+    }
+
+    #[test]
+    fn malformed_recordings_are_rejected() {
+        for text in ["variant Freecell", "jokers maybe", "draw 2", "seed -1", "seed", "1.0 q"] {
+            assert!(text.parse::<Recording>().is_err(), "`{text}` parsed");
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+
+use super::layout::Place;
+use super::piles::Pile;
+use super::systems::GameEvent;
+use super::{Board, Card, CardBundle};
+
+/// Canfield's reserve: a face-down stack whose top card is face up and can be played.
+#[derive(Component)]
+pub struct Reserve;
+
+/// Spawns the reserve with its top card already face up, as `flip_top_of_reserve` keeps it.
+pub fn spawn_reserve(commands: &mut Commands, cards: Vec<Card>, asset_server: &Res<AssetServer>) {
+    let mut parent = commands.spawn((
+        SpriteBundle {
+            transform: Transform::from_xyz(0.0, 0.0, -100.0),
+            texture: asset_server.load("cards/Back Blue 1.png"),
+            sprite: Sprite {
+                color: Color::Rgba { red: 0.5, green: 0.5, blue: 0.5, alpha: 0.25 },
+                ..default()
+            },
+            ..default()
+        },
+        Reserve,
+        Place::Tableau(0.0),
+        Board,
+        PickableBundle {
+            pickable: Pickable::IGNORE,
+            ..default()
+        },
+    )).id();
+
+    let top = cards.len().saturating_sub(1);
+    for (i, card) in cards.into_iter().enumerate() {
+        let face_up = i == top;
+        let child = commands.spawn((
+            CardBundle {
+                card,
+                sprite: SpriteBundle {
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    texture: if face_up {
+                        asset_server.load(card.texture())
+                    } else {
+                        asset_server.load("cards/Back Blue 1.png")
+                    },
+                    ..default()
+                },
+                pickable_bundle: PickableBundle {
+                    pickable: if face_up { Pickable::default() } else { Pickable::IGNORE },
+                    ..default()
+                }
+            },
+        )).id();
+        commands.entity(parent).add_child(child);
+        parent = child;
+    }
+}
+
+pub fn format_reserve(
+    q_reserve: &Query<&Children, With<Reserve>>,
+    q_children: &Query<&Children, With<Card>>,
+    transform_query: &mut Query<&mut Transform, With<Card>>
+) {
+    for children in q_reserve {
+        for &child in children {
+            if let Ok(mut transform) = transform_query.get_mut(child) {
+                transform.translation.x = 0.0;
+                transform.translation.y = 0.0;
+                transform.translation.z = 1.0;
+            }
+
+            for child in q_children.iter_descendants(child) {
+                if let Ok(mut transform) = transform_query.get_mut(child) {
+                    transform.translation.x = 0.0;
+                    transform.translation.y = 0.0;
+                    transform.translation.z = 1.0;
+                }
+            }
+        }
+    }
+}
+
+pub fn flip_top_of_reserve(
+    q_reserve: Query<Entity, With<Reserve>>,
+    q_children: Query<&Children>,
+    mut q_pickable: Query<(&mut Pickable, &mut Handle<Image>, &Card)>,
+    asset_server: Res<AssetServer>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    for reserve in q_reserve.iter() {
+        if let Some(top_card) = q_children.iter_descendants(reserve).last() {
+            if let Ok((mut pickable, mut texture, card)) = q_pickable.get_mut(top_card) {
+                pickable.set_if_neq(Pickable::default());
+                if texture.set_if_neq(asset_server.load(card.texture())) {
+                    game_event.send(GameEvent::Flipped);
+                }
+            }
+        }
+    }
+}
+
+/// Spaces in the tableau are filled straight away from the reserve.
+pub fn fill_from_reserve(
+    mut commands: Commands,
+    q_reserve: Query<Entity, With<Reserve>>,
+    q_piles: Query<(Entity, Option<&Children>), With<Pile>>,
+    q_children: Query<&Children>,
+    mut q_cards: Query<(&mut Transform, &mut Pickable, &mut Handle<Image>, &Card)>,
+    asset_server: Res<AssetServer>
+) {
+    let Ok(reserve) = q_reserve.get_single() else {
+        return;
+    };
+    let mut reserve_cards: Vec<Entity> = q_children.iter_descendants(reserve).collect();
+
+    for (pile, children) in q_piles.iter() {
+        if children.is_some_and(|children| !children.is_empty()) {
+            continue;
+        }
+        let Some(top_card) = reserve_cards.pop() else {
+            return;
+        };
+        if let Ok((mut transform, mut pickable, mut texture, card)) = q_cards.get_mut(top_card) {
+            commands.entity(pile).add_child(top_card);
+            *transform = Transform::from_xyz(0.0, 0.0, 1.0);
+            pickable.set_if_neq(Pickable::default());
+            texture.set_if_neq(asset_server.load(card.texture()));
+        }
+    }
+}
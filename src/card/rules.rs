@@ -0,0 +1,220 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+
+use super::foundation::Foundation;
+use super::peaks::{is_exposed, PeakSlot, Peaks};
+use super::piles::{Base, Pile};
+use super::stock::Stock;
+use super::variant::{Moves, Spaces, Variant};
+use super::waste::Waste;
+use super::{Card, CardFace};
+
+/// Decides whether a card may be moved somewhere, for every input, the hint and the move itself.
+#[derive(SystemParam)]
+#[allow(clippy::type_complexity)]
+pub struct Rules<'w, 's> {
+    pub variant: Res<'w, Variant>,
+    q_child: Query<'w, 's, &'static Children>,
+    q_parent: Query<'w, 's, &'static Parent>,
+    q_piles: Query<'w, 's, Entity, With<Pile>>,
+    q_cards: Query<'w, 's, &'static Card>,
+    q_bases: Query<'w, 's, (Entity, &'static Children, &'static Transform), (With<Base>, Without<Card>)>,
+    q_foundations: Query<'w, 's, (Entity, &'static Foundation)>,
+    q_stock: Query<'w, 's, (), With<Stock>>,
+    q_waste: Query<'w, 's, Entity, With<Waste>>,
+    q_peaks: Query<'w, 's, &'static Children, With<Peaks>>,
+    q_slots: Query<'w, 's, &'static PeakSlot>,
+    q_pickable: Query<'w, 's, &'static Pickable>,
+}
+
+fn is_neighbour(a: CardFace, b: CardFace, wraps: bool) -> bool {
+    let difference = a.0.abs_diff(b.0);
+    difference == 1 || (wraps && difference == 12)
+}
+
+impl Rules<'_, '_> {
+    /// What `card` would become a child of if it were dropped on `target`, if it's allowed.
+    pub fn destination(&self, card: Entity, target: Entity) -> Option<Entity> {
+        if self.variant.plays_to_waste() {
+            return self.played_to_waste(card, target);
+        }
+
+        if let Some(pile) = self.q_parent.iter_ancestors(target).find(|&parent| self.q_piles.contains(parent)) {
+            self.dropped_on_pile(card, pile)
+        } else if let Ok((_, children, _)) = self.q_bases.get(target) {
+            self.dropped_on_empty_pile(card, children)
+        } else if let Some(foundation) = self.q_parent.iter_ancestors(target).find(|&parent| self.q_foundations.contains(parent)) {
+            self.dropped_on_foundation(card, foundation)
+        } else if self.q_foundations.contains(target) {
+            self.dropped_on_foundation(card, target)
+        } else {
+            None
+        }
+    }
+
+    /// The card a joker resolves to once `card` has been placed on it at `destination`.
+    pub fn resolved_joker(&self, destination: Entity, card: Entity) -> Option<Card> {
+        let Ok(&Card::Joker { colour, stands_for: None }) = self.q_cards.get(destination) else {
+            return None;
+        };
+        let stands_for = self.variant.stand_in(self.face_up_below(destination), colour, self.q_cards.get(card).ok()?)?;
+        Some(Card::Joker { colour, stands_for: Some(stands_for) })
+    }
+
+    /// The face-up card `card` is built on, if it's on one.
+    fn face_up_below(&self, card: Entity) -> Option<&Card> {
+        let below = self.q_parent.get(card).ok()?.get();
+        let face_up = self.q_pickable.get(below).is_ok_and(|pickable| *pickable != Pickable::IGNORE);
+        self.q_cards.get(below).ok().filter(|_| face_up)
+    }
+
+    /// Whether `upper` can be built on `lower`, which may be a joker still to be resolved.
+    fn can_build_on(&self, lower: Entity, upper: &Card) -> bool {
+        match self.q_cards.get(lower) {
+            Ok(&Card::Joker { colour, stands_for: None }) => self.variant.stand_in(self.face_up_below(lower), colour, upper).is_some(),
+            Ok(lower) => self.variant.can_build(lower, upper),
+            Err(_) => false,
+        }
+    }
+
+    fn dropped_on_foundation(&self, card: Entity, foundation_entity: Entity) -> Option<Entity> {
+        let (_, foundation) = self.q_foundations.get(foundation_entity).ok()?;
+        let dropped_card = self.q_cards.get(card).ok()?;
+        let top_card = self.q_child.iter_descendants(foundation_entity).last();
+        if top_card == Some(card) || self.q_child.contains(card) {
+            return None;
+        }
+        let top = top_card.and_then(|top_card| self.q_cards.get(top_card).ok());
+        foundation.accepts(top, dropped_card).then_some(top_card.unwrap_or(foundation_entity))
+    }
+
+    fn dropped_on_empty_pile(&self, card: Entity, children: &Children) -> Option<Entity> {
+        let card_comp = self.q_cards.get(card).ok()?;
+        let fills_space = match self.variant.spaces() {
+            Spaces::Kings => card_comp.is_joker() || card_comp.face() == Some(CardFace(13)),
+            Spaces::AnyCard => true,
+            Spaces::Never => false,
+        };
+        let pile = children.first().copied()?;
+        (fills_space && self.is_movable_group(card) && self.q_child.iter_descendants(pile).next().is_none()).then_some(pile)
+    }
+
+    fn dropped_on_pile(&self, card: Entity, pile: Entity) -> Option<Entity> {
+        if let Some(other_pile) = self.q_parent.iter_ancestors(card).find(|&parent| self.q_piles.contains(parent)) {
+            if pile == other_pile {
+                return None;
+            }
+        }
+        self.move_to_pile(card, pile)
+    }
+
+    fn move_to_pile(&self, card: Entity, pile: Entity) -> Option<Entity> {
+        let last_card = self.q_child.iter_descendants(pile).last()?;
+        if last_card == card {
+            return None;
+        }
+        let dropped_card_comp = self.q_cards.get(card).ok()?;
+        (self.can_build_on(last_card, dropped_card_comp) && self.is_movable_group(card)).then_some(last_card)
+    }
+
+    /// Where a clicked card goes: a foundation, then the first pile it builds on, then an empty one.
+    pub fn best_target(&self, card: Entity) -> Option<Entity> {
+        if !self.can_pick_up(card) {
+            return None;
+        }
+
+        let mut piles: Vec<(bool, f32, Entity)> = self.q_bases.iter()
+            .filter_map(|(base, children, transform)| {
+                let pile = children.first().copied()?;
+                let top_card = self.q_child.iter_descendants(pile).last();
+                Some((top_card.is_none(), transform.translation.x, top_card.unwrap_or(base)))
+            })
+            .collect();
+        piles.sort_by(|a, b| a.0.cmp(&b.0).then(a.1.total_cmp(&b.1)));
+
+        self.q_foundations.iter()
+            .map(|(foundation, _)| foundation)
+            .chain(piles.into_iter().map(|(_, _, target)| target))
+            .find(|&target| {
+                self.destination(card, target).is_some_and(|destination| self.is_progress(card, destination))
+            })
+    }
+
+    /// Whether `entity` is a foundation or a card on one.
+    pub fn is_foundation(&self, entity: Entity) -> bool {
+        self.q_foundations.contains(entity)
+            || self.q_parent.iter_ancestors(entity).any(|parent| self.q_foundations.contains(parent))
+    }
+
+    /// Whether `card` can be picked up at all. Stock cards are drawn, not picked up.
+    pub fn can_pick_up(&self, card: Entity) -> bool {
+        if !self.q_cards.contains(card) || self.q_parent.iter_ancestors(card).any(|parent| self.q_stock.contains(parent)) {
+            return false;
+        }
+        let on_pile = self.q_parent.iter_ancestors(card).any(|parent| self.q_piles.contains(parent));
+        (on_pile || !self.q_child.contains(card)) && self.is_movable_group(card)
+    }
+
+    /// Whether moving `card` to `destination` does any good rather than shuffling cards around.
+    pub fn is_progress(&self, card: Entity, destination: Entity) -> bool {
+        let on_foundation = self.q_parent.iter_ancestors(card).any(|parent| self.q_foundations.contains(parent));
+        let whole_pile = self.q_parent.get(card).is_ok_and(|parent| self.q_piles.contains(parent.get()))
+            && self.q_piles.contains(destination);
+        !on_foundation && !whole_pile
+    }
+
+    /// How many cards from `card` up are built on each other, so a king to ace run is 13.
+    pub fn run_length(&self, card: Entity) -> usize {
+        let cards: Vec<&Card> = std::iter::once(card)
+            .chain(self.q_child.iter_descendants(card))
+            .filter_map(|entity| self.q_cards.get(entity).ok())
+            .collect();
+        1 + cards.windows(2)
+            .take_while(|pair| {
+                matches!((pair[0].face(), pair[1].face()), (Some(under), Some(over)) if under.0 == over.0 + 1)
+                    && self.variant.can_build(pair[0], pair[1])
+            })
+            .count()
+    }
+
+    /// Whether the variant lets `card` be moved along with the cards carried on top of it.
+    pub fn is_movable_group(&self, card: Entity) -> bool {
+        let group: Vec<&Card> = std::iter::once(card)
+            .chain(self.q_child.iter_descendants(card))
+            .filter_map(|entity| self.q_cards.get(entity).ok())
+            .collect();
+        match self.variant.moves() {
+            Moves::AnyGroup => true,
+            Moves::SingleCards => group.len() == 1,
+            Moves::Sequences => group.windows(2).all(|pair| self.variant.can_build(pair[0], pair[1])),
+        }
+    }
+
+    /// Golf and TriPeaks: an uncovered card can go on the waste one rank either side of its top card.
+    fn played_to_waste(&self, card: Entity, target: Entity) -> Option<Entity> {
+        let waste = self.q_waste.get_single().ok()?;
+        if target != waste && !self.q_parent.iter_ancestors(target).any(|parent| parent == waste) {
+            return None;
+        }
+
+        let from_pile = self.q_parent.iter_ancestors(card).any(|ancestor| self.q_piles.contains(ancestor))
+            && !self.q_child.contains(card);
+        let from_peaks = self.q_parent.get(card).ok()
+            .and_then(|parent| self.q_peaks.get(parent.get()).ok())
+            .is_some_and(|peaks| is_exposed(card, peaks, &self.q_slots));
+        if !from_pile && !from_peaks {
+            return None;
+        }
+
+        let top_card = self.q_child.iter_descendants(waste).last().unwrap_or(waste);
+        if let (Ok(card), Ok(top)) = (self.q_cards.get(card), self.q_cards.get(top_card)) {
+            if let (Some(face), Some(top_face)) = (card.face(), top.face()) {
+                if !is_neighbour(face, top_face, self.variant.wraps()) {
+                    return None;
+                }
+            }
+        }
+        Some(top_card)
+    }
+}
@@ -0,0 +1,129 @@
+use std::time::{Duration, Instant};
+
+use bevy::prelude::*;
+
+use super::golf::Streak;
+use super::systems::{Destination, GameEvent};
+use super::win::GameWon;
+use super::{GameState, Gameplay, NewGame};
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Score>()
+            // After the moves so that a win is scored in the frame it happens.
+            .add_systems(Update, (
+                time_score,
+                count_score,
+            ).chain().after(Gameplay));
+    }
+}
+
+/// How the current game is going: points scored, moves made and time taken.
+#[derive(Resource, Default, Clone, Debug, PartialEq)]
+pub struct Score {
+    pub points: u32,
+    pub moves: u32,
+    /// Seconds of play, not counting time spent paused or in the menus.
+    pub seconds: f32,
+    clock: Clock,
+    /// Dealt from a seed rather than loaded from a position, so it can go on the leaderboards.
+    pub ranked: bool,
+    pub won: bool,
+    /// A move was taken back, so the points can't go on the score table.
+    pub undone: bool,
+}
+
+/// Time that runs only while it's told to, read from the system clock.
+#[derive(Default, Clone, Debug, PartialEq)]
+struct Clock {
+    since: Option<Instant>,
+    banked: Duration,
+}
+
+impl Clock {
+    fn run(&mut self, running: bool) {
+        match (self.since, running) {
+            (None, true) => self.since = Some(Instant::now()),
+            (Some(since), false) => {
+                self.banked += since.elapsed();
+                self.since = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.banked + self.since.map_or(Duration::ZERO, |since| since.elapsed())
+    }
+}
+
+impl Score {
+    /// `m:ss`, or `h:mm:ss` past the hour.
+    pub fn time(seconds: f32) -> String {
+        let seconds = seconds as u32;
+        match seconds / 3600 {
+            0 => format!("{}:{:02}", seconds / 60, seconds % 60),
+            hours => format!("{hours}:{:02}:{:02}", seconds / 60 % 60, seconds % 60),
+        }
+    }
+}
+
+impl std::fmt::Display for Score {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} points, {} moves in {}", self.points, self.moves, Score::time(self.seconds))
+    }
+}
+
+/// The clock runs while the deal is being played, and stops once it's won.
+fn time_score(state: Res<State<GameState>>, mut score: ResMut<Score>) {
+    let running = *state.get() == GameState::Playing && !score.won;
+    score.clock.run(running);
+    score.seconds = score.clock.elapsed().as_secs_f32();
+}
+
+/// Ten points for each card to a foundation, five and the streak for each card played to the
+/// waste, five for a card turned over, a hundred off for going through the stock again and a
+/// bonus for a quick win.
+pub fn count_score(
+    mut new_game_event: EventReader<NewGame>,
+    mut game_event: EventReader<GameEvent>,
+    mut game_won_event: EventReader<GameWon>,
+    streak: Res<Streak>,
+    mut score: ResMut<Score>,
+) {
+    if let Some(&new_game) = new_game_event.read().last() {
+        *score = Score { ranked: new_game != NewGame::Load, ..default() };
+        // The deal itself isn't a move.
+        game_event.clear();
+    }
+    for &event in game_event.read() {
+        match event {
+            GameEvent::Moved { to, .. } => {
+                score.moves += 1;
+                score.points += match to {
+                    Destination::Foundation => 10,
+                    Destination::Waste => 5 + streak.current,
+                    Destination::Pile => 0,
+                };
+            }
+            GameEvent::Drew | GameEvent::Dealt => score.moves += 1,
+            GameEvent::Recycled => {
+                score.moves += 1;
+                score.points = score.points.saturating_sub(100);
+            }
+            GameEvent::Flipped => score.points += 5,
+            GameEvent::Undone => score.undone = true,
+            GameEvent::PickedUp | GameEvent::Rejected | GameEvent::Redone => {}
+        }
+    }
+    if game_won_event.read().last().is_some() && !score.won {
+        score.won = true;
+        score.clock.run(false);
+        score.seconds = score.clock.elapsed().as_secs_f32();
+        if score.seconds > 30.0 {
+            score.points += (700_000.0 / score.seconds) as u32;
+        }
+    }
+}
@@ -0,0 +1,84 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use super::layout::Handedness;
+use super::speedrun::Timing;
+
+/// Where settings and anything else the game keeps between runs are saved.
+const SAVE_DIR: &str = "saves";
+
+pub struct SettingsPlugin;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load::<Settings>("settings.ron"))
+            .add_systems(Update, save_settings);
+    }
+}
+
+/// Player preferences that are kept between runs.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct Settings {
+    /// From 0 to 1.
+    pub volume: f32,
+    pub muted: bool,
+    /// For the next deal.
+    pub timing: Timing,
+    pub handedness: Handedness,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings { volume: 0.5, muted: false, timing: Timing::Off, handedness: Handedness::RightHanded }
+    }
+}
+
+fn path(file: &str) -> PathBuf {
+    PathBuf::from(SAVE_DIR).join(file)
+}
+
+/// Reads `file` from the save directory, or the default if it isn't there or can't be read.
+pub fn load<T: DeserializeOwned + Default>(file: &str) -> T {
+    match read(file) {
+        Some(text) => ron::from_str(&text).unwrap_or_else(|error| {
+            warn!("Ignoring {file}: {error}");
+            T::default()
+        }),
+        None => T::default(),
+    }
+}
+
+pub fn save<T: Serialize>(file: &str, value: &T) {
+    match ron::ser::to_string_pretty(value, default()) {
+        Ok(text) => write(file, &text),
+        Err(error) => warn!("Couldn't save {file}: {error}"),
+    }
+}
+
+/// Reads `file` from the save directory as plain text.
+pub fn read(file: &str) -> Option<String> {
+    fs::read_to_string(path(file)).ok()
+}
+
+/// Writes plain text to `file` in the save directory.
+pub fn write(file: &str, text: &str) {
+    let result = fs::create_dir_all(SAVE_DIR).and_then(|_| fs::write(path(file), text));
+    if let Err(error) = result {
+        warn!("Couldn't save {file}: {error}");
+    }
+}
+
+pub fn exists(file: &str) -> bool {
+    path(file).exists()
+}
+
+fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        save("settings.ron", &*settings);
+    }
+}
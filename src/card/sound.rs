@@ -0,0 +1,209 @@
+use std::f32::consts::TAU;
+use std::time::Duration;
+
+use bevy::audio::{AddAudioSource, Source, Volume};
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use super::leaderboard::entering_name;
+use super::settings::Settings;
+use super::systems::{Destination, GameEvent};
+use super::win::GameWon;
+
+const SAMPLE_RATE: u32 = 44_100;
+
+pub struct SoundPlugin;
+
+impl Plugin for SoundPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_audio_source::<Tone>()
+            .add_systems(Startup, make_sounds)
+            .add_systems(Update, (volume_keys.run_if(not(entering_name)), play_sounds));
+    }
+}
+
+#[derive(EnumIter, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Sound {
+    PickUp,
+    Drop,
+    Invalid,
+    Flip,
+    Deal,
+    Draw,
+    Recycle,
+    Foundation,
+    Win,
+}
+
+impl Sound {
+    /// Taking a move back or making it again is silent.
+    fn for_event(event: GameEvent) -> Option<Sound> {
+        match event {
+            GameEvent::PickedUp => Some(Sound::PickUp),
+            GameEvent::Moved { to: Destination::Foundation, .. } => Some(Sound::Foundation),
+            GameEvent::Moved { .. } => Some(Sound::Drop),
+            GameEvent::Rejected => Some(Sound::Invalid),
+            GameEvent::Flipped => Some(Sound::Flip),
+            GameEvent::Dealt => Some(Sound::Deal),
+            GameEvent::Drew => Some(Sound::Draw),
+            GameEvent::Recycled => Some(Sound::Recycle),
+            GameEvent::Undone | GameEvent::Redone => None,
+        }
+    }
+
+    fn tone(self) -> Tone {
+        let swish = |from, to, seconds| Note { from, to, seconds, noise: 0.8 };
+        let beep = |frequency, seconds| Note { from: frequency, to: frequency, seconds, noise: 0.0 };
+        let notes = match self {
+            Sound::PickUp => vec![swish(600.0, 900.0, 0.05)],
+            Sound::Drop => vec![swish(300.0, 200.0, 0.07)],
+            Sound::Invalid => vec![beep(180.0, 0.1), beep(140.0, 0.12)],
+            Sound::Flip => vec![swish(1200.0, 800.0, 0.05)],
+            Sound::Deal => vec![swish(700.0, 500.0, 0.04); 4],
+            Sound::Draw => vec![swish(900.0, 600.0, 0.06)],
+            Sound::Recycle => vec![swish(400.0, 1200.0, 0.25)],
+            Sound::Foundation => vec![beep(660.0, 0.08), beep(880.0, 0.12)],
+            Sound::Win => vec![beep(523.0, 0.15), beep(659.0, 0.15), beep(784.0, 0.15), beep(1047.0, 0.3)],
+        };
+        Tone { notes }
+    }
+}
+
+/// One part of a `Tone`: a pitch sliding from `from` to `to` Hz, mixed with
+/// `noise` parts in one of white noise for the papery sounds.
+#[derive(Copy, Clone, Debug)]
+struct Note {
+    from: f32,
+    to: f32,
+    seconds: f32,
+    noise: f32,
+}
+
+/// A short sound made up on the fly, so that the game doesn't need any audio files.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct Tone {
+    notes: Vec<Note>,
+}
+
+impl Tone {
+    fn samples(&self) -> Vec<f32> {
+        let mut samples = vec![];
+        let mut phase = 0.0_f32;
+        let mut seed = 0x2545_f491_u32;
+        for note in &self.notes {
+            let count = (note.seconds * SAMPLE_RATE as f32) as usize;
+            for i in 0..count {
+                let t = i as f32 / count as f32;
+                let frequency = note.from + (note.to - note.from) * t;
+                phase = (phase + frequency / SAMPLE_RATE as f32).fract();
+
+                // Xorshift, which is plenty random enough for noise.
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                let noise = seed as f32 / u32::MAX as f32 * 2.0 - 1.0;
+
+                // A sharp attack, then dying away.
+                let envelope = (t * 50.0).min(1.0) * (1.0 - t).powi(2);
+                let sample = (phase * TAU).sin() * (1.0 - note.noise) + noise * note.noise;
+                samples.push(sample * envelope * 0.5);
+            }
+        }
+        samples
+    }
+}
+
+pub struct ToneDecoder {
+    samples: std::vec::IntoIter<f32>,
+}
+
+impl Iterator for ToneDecoder {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        self.samples.next()
+    }
+}
+
+impl Source for ToneDecoder {
+    fn current_frame_len(&self) -> Option<usize> {
+        None
+    }
+
+    fn channels(&self) -> u16 {
+        1
+    }
+
+    fn sample_rate(&self) -> u32 {
+        SAMPLE_RATE
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        None
+    }
+}
+
+impl Decodable for Tone {
+    type DecoderItem = f32;
+    type Decoder = ToneDecoder;
+
+    fn decoder(&self) -> Self::Decoder {
+        ToneDecoder { samples: self.samples().into_iter() }
+    }
+}
+
+#[derive(Resource)]
+struct Sounds(HashMap<Sound, Handle<Tone>>);
+
+fn make_sounds(mut commands: Commands, mut tones: ResMut<Assets<Tone>>) {
+    commands.insert_resource(Sounds(Sound::iter().map(|sound| (sound, tones.add(sound.tone()))).collect()));
+}
+
+/// M mutes, minus and plus turn the volume down and up.
+fn volume_keys(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<Settings>) {
+    if keys.just_pressed(KeyCode::KeyM) {
+        settings.muted = !settings.muted;
+    }
+    if keys.any_just_pressed([KeyCode::Minus, KeyCode::NumpadSubtract]) {
+        settings.volume = (settings.volume - 0.1).max(0.0);
+    }
+    if keys.any_just_pressed([KeyCode::Equal, KeyCode::NumpadAdd]) {
+        settings.volume = (settings.volume + 0.1).min(1.0);
+    }
+}
+
+fn play_sounds(
+    mut commands: Commands,
+    mut game_event: EventReader<GameEvent>,
+    mut game_won_event: EventReader<GameWon>,
+    sounds: Option<Res<Sounds>>,
+    settings: Res<Settings>,
+) {
+    // A single move can flip or deal several cards at once, which should still sound like one.
+    let mut playing: Vec<Sound> = vec![];
+    for sound in game_event.read().filter_map(|&event| Sound::for_event(event)) {
+        if !playing.contains(&sound) {
+            playing.push(sound);
+        }
+    }
+    if game_won_event.read().count() > 0 {
+        playing.push(Sound::Win);
+    }
+    let Some(sounds) = sounds else {
+        return;
+    };
+    if settings.muted || settings.volume <= 0.0 {
+        return;
+    }
+
+    for sound in playing {
+        if let Some(source) = sounds.0.get(&sound) {
+            commands.spawn(AudioSourceBundle {
+                source: source.clone(),
+                settings: PlaybackSettings::DESPAWN.with_volume(Volume::new(settings.volume)),
+            });
+        }
+    }
+}
@@ -0,0 +1,359 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::foundation::Foundation;
+use super::leaderboard::Mode;
+use super::score::{count_score, Score};
+use super::settings::{load, save, Settings};
+use super::variant::{HouseRules, Variant, WinCondition};
+use super::{Card, GameState, NewGame};
+
+/// The timings that can be picked, in the order they're cycled through.
+const TIMINGS: [Timing; 5] = [
+    Timing::Off,
+    Timing::Countdown(300),
+    Timing::Countdown(600),
+    Timing::Target(180),
+    Timing::Target(300),
+];
+const SUIT: usize = 13;
+const BAR_WIDTH: f32 = 200.0;
+
+pub struct SpeedRunPlugin;
+
+impl Plugin for SpeedRunPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load::<PersonalBests>("speedruns.ron"))
+            .init_resource::<Run>()
+            .add_systems(Startup, spawn_hud)
+            .add_systems(Update, (
+                track_run.after(count_score),
+                save_bests,
+                run_out.run_if(in_state(GameState::Playing)),
+                show_hud,
+            ).chain());
+    }
+}
+
+/// How the clock is shown, and whether it can run out. Changes take effect from the next deal.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq)]
+pub enum Timing {
+    #[default]
+    Off,
+    /// The deal is lost if it isn't won within this many seconds.
+    Countdown(u32),
+    /// A time in seconds to beat.
+    Target(u32),
+}
+
+impl Timing {
+    pub fn next(self) -> Timing {
+        let index = TIMINGS.iter().position(|&timing| timing == self).map_or(0, |index| index + 1);
+        TIMINGS[index % TIMINGS.len()]
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Timing::Off => write!(f, "off"),
+            Timing::Countdown(seconds) => write!(f, "{} minute countdown", seconds / 60),
+            Timing::Target(seconds) => write!(f, "beat {}", Score::time(seconds as f32)),
+        }
+    }
+}
+
+/// A point on the way to winning a deal that goes to the foundations.
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub enum Milestone {
+    /// Every foundation has its first card.
+    AllAces,
+    /// This many foundations have been completed.
+    Suits(usize),
+    Finish,
+}
+
+impl fmt::Display for Milestone {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Milestone::AllAces => write!(f, "All aces"),
+            Milestone::Suits(1) => write!(f, "1 suit"),
+            Milestone::Suits(suits) => write!(f, "{suits} suits"),
+            Milestone::Finish => write!(f, "Finish"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq)]
+pub struct Split {
+    pub milestone: Milestone,
+    pub seconds: f32,
+}
+
+/// The fastest win of a mode.
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Best {
+    pub seconds: f32,
+    pub splits: Vec<Split>,
+    /// When each card reached the foundations, for the ghost.
+    pub cards: Vec<f32>,
+}
+
+impl Best {
+    fn split(&self, milestone: Milestone) -> Option<f32> {
+        self.splits.iter().find(|split| split.milestone == milestone).map(|split| split.seconds)
+    }
+}
+
+/// The fastest win of every mode, kept between runs.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct PersonalBests {
+    pub modes: HashMap<Mode, Best>,
+}
+
+/// The timing of the current deal.
+#[derive(Resource, Default)]
+pub struct Run {
+    /// The timing the deal was dealt with.
+    pub timing: Timing,
+    pub splits: Vec<Split>,
+    /// When each card first reached the foundations.
+    cards: Vec<f32>,
+}
+
+impl Run {
+    fn reached(&self, milestone: Milestone) -> bool {
+        self.splits.iter().any(|split| split.milestone == milestone)
+    }
+}
+
+#[derive(Component)]
+struct Hud;
+
+#[derive(Component)]
+struct ClockText;
+
+#[derive(Component)]
+struct SplitsText;
+
+/// The bars comparing the cards on the foundations with the personal best's at the same time.
+#[derive(Component)]
+struct Bars;
+
+#[derive(Component, Copy, Clone, PartialEq)]
+enum Fill {
+    Player,
+    Ghost,
+}
+
+/// Only deals played from a seed without taking a move back can set a personal best, and
+/// never while watching a replay.
+#[allow(clippy::too_many_arguments)]
+fn track_run(
+    mut new_game_event: EventReader<NewGame>,
+    state: Res<State<GameState>>,
+    settings: Res<Settings>,
+    score: Res<Score>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    q_foundations: Query<Entity, With<Foundation>>,
+    q_children: Query<&Children>,
+    mut run: ResMut<Run>,
+    mut bests: ResMut<PersonalBests>,
+) {
+    if new_game_event.read().last().is_some() {
+        *run = Run { timing: settings.timing, ..default() };
+        return;
+    }
+    if *state.get() != GameState::Playing || run.reached(Milestone::Finish) {
+        return;
+    }
+
+    let foundations: Vec<usize> = q_foundations.iter()
+        .map(|foundation| q_children.iter_descendants(foundation).count())
+        .collect();
+    let on_foundations: usize = foundations.iter().sum();
+    if on_foundations > run.cards.len() {
+        let seconds = score.seconds;
+        run.cards.resize(on_foundations, seconds);
+    }
+    let mut reached = vec![];
+    if !foundations.is_empty() && foundations.iter().all(|&cards| cards > 0) {
+        reached.push(Milestone::AllAces);
+    }
+    let suits = foundations.iter().filter(|&&cards| cards >= SUIT).count();
+    reached.extend((1..=suits).map(Milestone::Suits));
+    if score.won {
+        reached.push(Milestone::Finish);
+    }
+    for milestone in reached {
+        if !run.reached(milestone) {
+            run.splits.push(Split { milestone, seconds: score.seconds });
+        }
+    }
+
+    if !score.won || !score.ranked || score.undone || *state.get() == GameState::Replay {
+        return;
+    }
+    let mode = Mode::new(*variant, *house_rules);
+    if bests.modes.get(&mode).is_none_or(|best| score.seconds < best.seconds) {
+        bests.modes.insert(mode, Best { seconds: score.seconds, splits: run.splits.clone(), cards: run.cards.clone() });
+    }
+}
+
+fn save_bests(bests: Res<PersonalBests>) {
+    if bests.is_changed() && !bests.is_added() {
+        save("speedruns.ron", &*bests);
+    }
+}
+
+/// A countdown that reaches zero loses the deal.
+fn run_out(run: Res<Run>, score: Res<Score>, mut next_state: ResMut<NextState<GameState>>) {
+    if let Timing::Countdown(limit) = run.timing {
+        if !score.won && score.seconds >= limit as f32 {
+            next_state.set(GameState::TimeUp);
+        }
+    }
+}
+
+/// `m:ss.s`, with a sign for the difference from a personal best.
+fn precise(seconds: f32, sign: bool) -> String {
+    let sign = match sign {
+        true if seconds < 0.0 => "-",
+        true => "+",
+        false => "",
+    };
+    let seconds = seconds.abs();
+    format!("{sign}{}:{:04.1}", (seconds / 60.0) as u32, seconds % 60.0)
+}
+
+fn spawn_hud(mut commands: Commands) {
+    let style = |size| TextStyle { font_size: size, color: Color::WHITE, ..default() };
+    commands.spawn((
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                left: Val::Px(16.0),
+                bottom: Val::Px(16.0),
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(4.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.05, 0.2, 0.1, 0.8).into(),
+            visibility: Visibility::Hidden,
+            z_index: ZIndex::Global(5),
+            ..default()
+        },
+        Hud,
+    )).with_children(|hud| {
+        hud.spawn((TextBundle::from_section("", style(32.0)), ClockText));
+        hud.spawn((TextBundle::from_section("", style(18.0)), SplitsText));
+        hud.spawn((
+            NodeBundle {
+                style: Style { flex_direction: FlexDirection::Column, row_gap: Val::Px(4.0), ..default() },
+                ..default()
+            },
+            Bars,
+        )).with_children(|bars| {
+            for (label, fill, colour) in [("You", Fill::Player, Color::rgb(0.9, 0.8, 0.3)), ("Best", Fill::Ghost, Color::rgba(1.0, 1.0, 1.0, 0.5))] {
+                bars.spawn(NodeBundle {
+                    style: Style { column_gap: Val::Px(8.0), align_items: AlignItems::Center, ..default() },
+                    ..default()
+                }).with_children(|row| {
+                    row.spawn(TextBundle::from_section(label, style(16.0)).with_style(Style { width: Val::Px(40.0), ..default() }));
+                    row.spawn(NodeBundle {
+                        style: Style { width: Val::Px(BAR_WIDTH), height: Val::Px(10.0), ..default() },
+                        background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                        ..default()
+                    }).with_children(|bar| {
+                        bar.spawn((
+                            NodeBundle {
+                                style: Style { width: Val::Percent(0.0), height: Val::Percent(100.0), ..default() },
+                                background_color: colour.into(),
+                                ..default()
+                            },
+                            fill,
+                        ));
+                    });
+                });
+            }
+        });
+    });
+}
+
+/// The clock, the splits and the personal best's ghost, shown only when timing is on.
+#[allow(clippy::too_many_arguments)]
+fn show_hud(
+    state: Res<State<GameState>>,
+    run: Res<Run>,
+    score: Res<Score>,
+    bests: Res<PersonalBests>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    q_cards: Query<&Card>,
+    mut q_hud: Query<&mut Visibility, With<Hud>>,
+    mut q_clock: Query<&mut Text, (With<ClockText>, Without<SplitsText>)>,
+    mut q_splits: Query<&mut Text, (With<SplitsText>, Without<ClockText>)>,
+    mut q_bars: Query<&mut Style, (With<Bars>, Without<Fill>)>,
+    mut q_fills: Query<(&mut Style, &Fill), Without<Bars>>,
+) {
+    let shown = run.timing != Timing::Off
+        && matches!(state.get(), GameState::Playing | GameState::Won | GameState::TimeUp);
+    for mut visibility in q_hud.iter_mut() {
+        visibility.set_if_neq(if shown { Visibility::Inherited } else { Visibility::Hidden });
+    }
+    if !shown {
+        return;
+    }
+
+    let seconds = score.seconds;
+    let clock = match run.timing {
+        Timing::Off => String::new(),
+        Timing::Countdown(limit) => format!("{} left", precise((limit as f32 - seconds).max(0.0), false)),
+        Timing::Target(target) => format!("{} / {}", precise(seconds, false), Score::time(target as f32)),
+    };
+    let best = bests.modes.get(&Mode::new(*variant, *house_rules));
+    let splits: Vec<String> = run.splits.iter().map(|split| {
+        match best.and_then(|best| best.split(split.milestone)) {
+            Some(best) => format!("{} {} ({})", split.milestone, precise(split.seconds, false), precise(split.seconds - best, true)),
+            None => format!("{} {}", split.milestone, precise(split.seconds, false)),
+        }
+    }).collect();
+    let splits = splits.join("\n");
+    for mut text in q_clock.iter_mut() {
+        if text.sections[0].value != clock {
+            text.sections[0].value.clone_from(&clock);
+        }
+    }
+    for mut text in q_splits.iter_mut() {
+        if text.sections[0].value != splits {
+            text.sections[0].value.clone_from(&splits);
+        }
+    }
+
+    // Only variants that go to the foundations have anything to race.
+    let total = q_cards.iter().filter(|card| !card.is_joker()).count();
+    let racing = variant.win_condition() == WinCondition::Foundations && total > 0;
+    let display = if racing { Display::Flex } else { Display::None };
+    for mut style in q_bars.iter_mut() {
+        if style.display != display {
+            style.display = display;
+        }
+    }
+    for (mut style, &fill) in q_fills.iter_mut() {
+        let cards = match fill {
+            Fill::Player => run.cards.len(),
+            Fill::Ghost => best.map_or(0, |best| best.cards.iter().filter(|&&at| at <= seconds).count()),
+        };
+        let width = Val::Percent(100.0 * cards.min(total) as f32 / total.max(1) as f32);
+        if style.width != width {
+            style.width = width;
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::settings::{load, save};
+use super::variant::Variant;
+use super::win::GameWon;
+use super::{GameState, NewGame};
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(load::<Stats>("stats.ron"))
+            .add_systems(Update, (count_games, save_stats).chain());
+    }
+}
+
+/// Games played and won of each variant, kept between runs.
+#[derive(Resource, Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct Stats {
+    pub variants: HashMap<Variant, Record>,
+}
+
+#[derive(Serialize, Deserialize, Default, Copy, Clone, Debug, PartialEq)]
+pub struct Record {
+    /// Every deal counts as played, so a deal given up on counts as a loss.
+    pub played: u32,
+    pub won: u32,
+}
+
+fn count_games(
+    mut new_game_event: EventReader<NewGame>,
+    mut game_won_event: EventReader<GameWon>,
+    variant: Res<Variant>,
+    state: Res<State<GameState>>,
+    mut stats: ResMut<Stats>,
+) {
+    // Watching a replay doesn't count as playing.
+    if *state.get() == GameState::Replay {
+        new_game_event.clear();
+        game_won_event.clear();
+        return;
+    }
+    // Restarting is another go at the same game rather than a new one.
+    if new_game_event.read().any(|&new_game| new_game == NewGame::Deal) {
+        stats.variants.entry(*variant).or_default().played += 1;
+    }
+    if game_won_event.read().last().is_some() {
+        stats.variants.entry(*variant).or_default().won += 1;
+    }
+}
+
+fn save_stats(stats: Res<Stats>) {
+    if stats.is_changed() && !stats.is_added() {
+        save("stats.ron", &*stats);
+    }
+}
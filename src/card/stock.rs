@@ -1,6 +1,9 @@
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
 
+use super::layout::Place;
+use super::piles::{Base, Pile};
+use super::systems::{Activate, GameEvent};
 use super::{Board, Card, CardBundle};
 
 #[derive(Component)]
@@ -9,7 +12,7 @@ pub struct Stock;
 pub fn spawn_stock(commands: &mut Commands, stock: Vec<Card>, asset_server: &Res<AssetServer>) {
     commands.spawn((
         SpriteBundle {
-            transform: Transform::from_xyz(-500.0, 275.0, -100.0),
+            transform: Transform::from_xyz(0.0, 0.0, -100.0),
             texture: asset_server.load("cards/Back Blue 1.png"),
             sprite: Sprite {
                 color: Color::Rgba { red: 0.5, green: 0.5, blue: 0.5, alpha: 0.25 },
@@ -18,6 +21,7 @@ pub fn spawn_stock(commands: &mut Commands, stock: Vec<Card>, asset_server: &Res
             ..default()
         },
         Stock,
+        Place::Top(0.0),
         Board
     ))
     .with_children(|parent| {
@@ -31,9 +35,50 @@ pub fn spawn_stock(commands: &mut Commands, stock: Vec<Card>, asset_server: &Res
                     },
                     pickable_bundle: PickableBundle::default()
                 },
-                On::<Pointer<DragStart>>::target_insert(Pickable::IGNORE), // Disable picking
-                On::<Pointer<DragEnd>>::target_insert(Pickable::default()), // Re-enable picking
             ));
         }
     });
-}
\ No newline at end of file
+}
+
+/// Deals the whole stock onto the tableau, one card to each pile from the left.
+#[allow(clippy::too_many_arguments)]
+pub fn deal_stock_to_piles(
+    mut commands: Commands,
+    mut activate_event: EventReader<Activate>,
+    q_stock: Query<(Entity, Option<&Children>), With<Stock>>,
+    q_piles: Query<(Entity, &Parent), With<Pile>>,
+    q_bases: Query<&Place, With<Base>>,
+    q_children: Query<&Children>,
+    mut q_cards: Query<(&mut Transform, &mut Handle<Image>, &Card)>,
+    asset_server: Res<AssetServer>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    for &Activate(target) in activate_event.read() {
+        let Ok((stock, Some(stock_cards))) = q_stock.get_single() else {
+            continue;
+        };
+        if target != stock && !stock_cards.contains(&target) {
+            continue;
+        }
+
+        let mut piles: Vec<(f32, Entity)> = q_piles.iter()
+            .filter_map(|(pile, base)| q_bases.get(base.get()).ok().map(|place| (place.column(), pile)))
+            .collect();
+        piles.sort_by(|a, b| a.0.total_cmp(&b.0));
+        game_event.send(GameEvent::Dealt);
+
+        for (&card_entity, (_, pile)) in stock_cards.iter().rev().zip(piles) {
+            if let Ok((mut transform, mut texture, card)) = q_cards.get_mut(card_entity) {
+                // `format_piles` fans the dealt cards out.
+                if let Some(top_card) = q_children.iter_descendants(pile).last() {
+                    commands.entity(top_card).add_child(card_entity);
+                    *transform = Transform::from_xyz(0.0, 0.0, 10.0);
+                } else {
+                    commands.entity(pile).add_child(card_entity);
+                    *transform = Transform::from_xyz(0.0, 0.0, 1.0);
+                }
+                texture.set_if_neq(asset_server.load(card.texture()));
+            }
+        }
+    }
+}
@@ -2,192 +2,257 @@ use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
 
 use super::foundation::{Foundation, format_foundation};
+use super::golf::{play_to_waste, reset_streak, show_streak, spawn_streak_text};
+use super::layout::Layout;
+use super::peaks::flip_exposed_peaks;
+use super::notation::{play_moves, position_keys};
 use super::piles::*;
-use super::stock::Stock;
+use super::replay::{play_replay, record_moves};
+use super::reserve::{fill_from_reserve, flip_top_of_reserve, format_reserve, Reserve};
+use super::stock::{deal_stock_to_piles, Stock};
+use super::rules::Rules;
+use super::touch::touch_input;
+use super::variant::{HouseRules, Variant};
 use super::waste::{format_waste, Waste};
-use super::{Card, CardFace, CardSuit};
+use super::win::{announce_win, check_win, reset_won, GameWon};
+use super::{Card, GameState, Gameplay, Input};
 
 pub struct SystemsPlugin;
 
+/// A click on a card or pile, from the mouse or any other input.
+#[derive(Event)]
+pub struct Activate(pub Entity);
+
+/// A request to move `card`, along with everything on top of it, onto `target`.
+#[derive(Event)]
+pub struct MoveRequest {
+    pub card: Entity,
+    pub target: Entity,
+}
+
+/// Something that happened to the cards, for sounds, statistics and the like.
+#[derive(Event, Copy, Clone, Debug, PartialEq)]
+pub enum GameEvent {
+    PickedUp,
+    /// `run` is how many of the cards moved go down one rank at a time.
+    Moved { to: Destination, run: usize },
+    /// A move the rules don't allow, so the card went back where it came from.
+    Rejected,
+    Flipped,
+    /// Cards were dealt out, either for a new game or from the stock onto the piles.
+    Dealt,
+    /// A card was turned from the stock onto the waste.
+    Drew,
+    /// The waste was turned back over to make the stock again.
+    Recycled,
+    /// The last move was taken back.
+    Undone,
+    /// A move that was taken back was made again.
+    Redone,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Destination {
+    Pile,
+    Foundation,
+    Waste,
+}
+
 impl Plugin for SystemsPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (handle_click_event, handle_drag_event, move_card_drag_drop_event, flip_last_card_of_piles, handle_drag_end_event).chain());
+        app.add_event::<GameWon>()
+            .add_event::<Activate>()
+            .add_event::<MoveRequest>()
+            .add_event::<GameEvent>()
+            .add_systems(Update, (
+                pointer_input,
+                touch_input,
+                click_to_move.run_if(drags),
+                position_keys,
+            ).chain().in_set(Input))
+            .add_systems(Update, (
+                record_moves.run_if(in_state(GameState::Playing)),
+                play_replay.run_if(in_state(GameState::Replay)),
+                play_moves.run_if(in_state(GameState::Playing)),
+                handle_click_event,
+                deal_stock_to_piles.run_if(deals_stock_to_piles),
+                play_to_waste.run_if(plays_to_waste),
+                move_card_event.run_if(drags),
+                flip_last_card_of_piles,
+                fill_from_reserve,
+                flip_top_of_reserve,
+                flip_exposed_peaks,
+                handle_drag_end_event,
+                check_win,
+                announce_win
+            ).chain().in_set(Gameplay).after(Input))
+            .add_systems(Startup, spawn_streak_text)
+            .add_systems(Update, (reset_streak, reset_won, show_streak));
     }
 }
 
-fn handle_drag_event(
-    mut drag_event: EventReader<Pointer<Drag>>,
-    mut transform_query: Query<&mut Transform, With<Card>>,
-    q_foundation: Query<Entity, With<Foundation>>,
-    q_children: Query<&Children>
+pub fn drags(variant: Res<Variant>) -> bool {
+    variant.drags()
+}
+
+fn plays_to_waste(variant: Res<Variant>) -> bool {
+    variant.plays_to_waste()
+}
+
+fn deals_stock_to_piles(variant: Res<Variant>) -> bool {
+    variant.deals_stock_to_piles()
+}
+
+/// How far a pointer can move between press and release and still count as a click.
+const CLICK_SLOP: f32 = 12.0;
+
+/// The pointers that finished a drag this frame, so letting go of a card isn't also a click.
+pub fn dragged_pointers(drag_end_event: &mut EventReader<Pointer<DragEnd>>) -> Vec<PointerId> {
+    drag_end_event.read()
+        .filter(|drag_end| drag_end.distance.length() > CLICK_SLOP)
+        .map(|drag_end| drag_end.pointer_id)
+        .collect()
+}
+
+fn pointer_input(
+    mut click_event: EventReader<Pointer<Click>>,
+    mut drag_end_event: EventReader<Pointer<DragEnd>>,
+    mut activate_event: EventWriter<Activate>,
 ) {
-    for drag in drag_event.read() {
-        for foundation in q_foundation.iter() {
-            if let Some(top_card) = q_children.iter_descendants(foundation).last() {
-                if drag.target == top_card {
-                    if let Ok(mut transform) = transform_query.get_mut(drag.target) {
-                        transform.translation.x += drag.delta.x;
-                        transform.translation.y -= drag.delta.y;
-                        transform.translation.z = 1000.0;
-                    }
-                }
-            } 
-        }
+    let dragged = dragged_pointers(&mut drag_end_event);
+    // Taps are handled by `touch_input`.
+    for click in click_event.read().filter(|click| !click.pointer_id.is_touch() && !dragged.contains(&click.pointer_id)) {
+        activate_event.send(Activate(click.target));
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_click_event(
     mut commands: Commands,
-    mut click_event: EventReader<Pointer<Click>>,
+    mut activate_event: EventReader<Activate>,
     q_stock: Query<(Entity, Option<&Children>), With<Stock>>,
     q_waste: Query<(Entity, Option<&Children>), With<Waste>>,
     q_children: Query<&Children, With<Card>>,
     mut transform_query: Query<(&mut Transform, &mut Handle<Image>, &Card), With<Card>>,
     asset_server: Res<AssetServer>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
+    mut game_event: EventWriter<GameEvent>,
 ) {
-    for click in click_event.read() {
-        let (stock, opt_children) = q_stock.single();
+    for &Activate(target) in activate_event.read() {
+        let Ok((stock, opt_children)) = q_stock.get_single() else {
+            continue;
+        };
         if let Some(children) = opt_children {
-            if children.contains(&click.target) {
-                let (waste, waste_children) = q_waste.single();
-                if let Some(waste_children) = waste_children {
-                    for &child in waste_children {
-                        if let Some(top_card) = q_children.iter_descendants(child).last() {
-                            add_child_to_waste(&mut transform_query, click, &mut commands, top_card, &asset_server);
-                        } else {
-                            add_child_to_waste(&mut transform_query, click, &mut commands, child, &asset_server);
-                        }
-                    }
-                } else {
-                    add_child_to_waste(&mut transform_query, click, &mut commands, waste, &asset_server);
+            if children.contains(&target) {
+                let Ok((waste, waste_children)) = q_waste.get_single() else {
+                    continue;
+                };
+                let mut top_card = waste_children.and_then(|children| children.first()).map_or(waste, |&child| {
+                    q_children.iter_descendants(child).last().unwrap_or(child)
+                });
+                // Cards are turned over one at a time from the top of the stock, so the last
+                // one drawn ends up on top of the waste.
+                let z = |card| transform_query.get(card).map_or(0.0, |(transform, _, _)| transform.translation.z);
+                let mut rest: Vec<Entity> = children.iter().copied().filter(|&card| card != target).collect();
+                rest.sort_by(|&a, &b| z(b).total_cmp(&z(a)));
+                let drawn: Vec<Entity> = std::iter::once(target).chain(rest).take(house_rules.draw(*variant)).collect();
+                for card in drawn {
+                    add_child_to_waste(&mut transform_query, card, &mut commands, top_card, &asset_server);
+                    top_card = card;
                 }
+                game_event.send(GameEvent::Drew);
             }
-        } else {
-            if stock == click.target {
-                let (_, waste_children) = q_waste.single();
-                if let Some(waste_children) = waste_children {
-                    for &child in waste_children {
+        } else if variant.redeals() && stock == target {
+            let (_, waste_children) = q_waste.single();
+            if let Some(waste_children) = waste_children {
+                game_event.send(GameEvent::Recycled);
+                for &child in waste_children {
+                    if let Ok((mut transform, mut texture, _)) = transform_query.get_mut(child) {
+                        commands.entity(stock).add_child(child);
+                        texture.set_if_neq(asset_server.load("cards/Back Blue 1.png"));
+                        transform.translation.z = 10.0;
+                    }
+
+                    for (i, child) in q_children.iter_descendants(child).enumerate() {
                         if let Ok((mut transform, mut texture, _)) = transform_query.get_mut(child) {
                             commands.entity(stock).add_child(child);
                             texture.set_if_neq(asset_server.load("cards/Back Blue 1.png"));
-                            transform.translation.z = 10.0;
-                        }
-
-                        for (i, child) in q_children.iter_descendants(child).enumerate() {
-                            if let Ok((mut transform, mut texture, _)) = transform_query.get_mut(child) {
-                                commands.entity(stock).add_child(child);
-                                texture.set_if_neq(asset_server.load("cards/Back Blue 1.png"));
-                                transform.translation.z = 10.0 - 0.1 * (i as f32);
-                            }
+                            transform.translation.z = 10.0 - 0.1 * (i as f32);
                         }
-
                     }
+
                 }
             }
         }
     }
 }
 
-fn add_child_to_waste(transform_query: &mut Query<(&mut Transform, &mut Handle<Image>, &Card), With<Card>>, click: &Pointer<Click>, commands: &mut Commands, entity: Entity, asset_server: &Res<AssetServer>) {
-    if let Ok((mut transform, mut texture, card)) = transform_query.get_mut(click.target) {
-        commands.entity(entity).add_child(click.target);
-        texture.set_if_neq(asset_server.load(format!("cards/{} {}.png", card.suit.to_string(), card.face.0)));
+fn add_child_to_waste(transform_query: &mut Query<(&mut Transform, &mut Handle<Image>, &Card), With<Card>>, card_entity: Entity, commands: &mut Commands, entity: Entity, asset_server: &Res<AssetServer>) {
+    if let Ok((mut transform, mut texture, card)) = transform_query.get_mut(card_entity) {
+        commands.entity(entity).add_child(card_entity);
+        texture.set_if_neq(asset_server.load(card.texture()));
         transform.translation.z = 1.0;
     }
 }
 
+/// Lines the cards up again after a drag, a move, a deal or a change of layout.
+#[allow(clippy::too_many_arguments)]
 fn handle_drag_end_event(
     mut drag_end_event: EventReader<Pointer<DragEnd>>,
+    mut move_request_event: EventReader<MoveRequest>,
+    q_moved: Query<(), (With<Card>, Changed<Parent>)>,
+    layout: Res<Layout>,
     q_pile: Query<&Children, With<Pile>>,
     q_children: Query<&Children, With<Card>>,
     q_foundation: Query<&Children, With<Foundation>>,
     q_waste: Query<&Children, With<Waste>>,
+    q_reserve: Query<&Children, With<Reserve>>,
     mut transform_query: Query<&mut Transform, With<Card>>,
+    q_textures: Query<&Handle<Image>, With<Card>>,
+    asset_server: Res<AssetServer>,
+    variant: Res<Variant>,
+    house_rules: Res<HouseRules>,
 ) {
-    for _ in drag_end_event.read() {
-        format_piles(&q_pile, &q_children, &mut transform_query);
+    let drag_ended = drag_end_event.read().count() > 0;
+    let moved = move_request_event.read().count() > 0 || !q_moved.is_empty();
+    if drag_ended || moved || layout.is_changed() || house_rules.is_changed() {
+        format_piles(&q_pile, &q_children, &mut transform_query, &q_textures, &asset_server.load("cards/Back Blue 1.png"), &layout);
         format_foundation(&q_foundation, &q_children, &mut transform_query);
-        format_waste(&q_waste, &q_children, &mut transform_query);
+        format_waste(&q_waste, &q_children, &mut transform_query, house_rules.draw(*variant), layout.waste_fan());
+        format_reserve(&q_reserve, &q_children, &mut transform_query);
     }
 }
 
-fn move_card_drag_drop_event(
-    mut commands: Commands,
-    mut drag_drop_event: EventReader<Pointer<Drop>>,
-    q_child: Query<&Children>,
-    q_parent: Query<&Parent>,
-    q_piles: Query<Entity, With<Pile>>,
-    q_cards: Query<&Card>,
-    q_bases: Query<(Entity, &Children), With<Base>>,
-    q_foundations: Query<&Foundation>
+/// A click on a card that can be picked up moves it to its best destination, if it has one.
+fn click_to_move(
+    mut activate_event: EventReader<Activate>,
+    rules: Rules,
+    mut move_request_event: EventWriter<MoveRequest>,
 ) {
-    for drop in drag_drop_event.read() {
-        if let Some(parent) = q_parent.iter_ancestors(drop.target).find(|&parent| q_piles.contains(parent)) {
-            dropped_on_pile(&q_parent, drop, &q_piles, parent, &q_child, &q_cards, &mut commands);
-        } else if let Ok((_,  children)) = q_bases.get(drop.target)  {
-            dropped_on_empty_pile(&q_cards, drop, children, &mut commands);
-        } else if let Some(parent) = q_parent.iter_ancestors(drop.target).find(|&parent| q_foundations.contains(parent)) {
-            if let Ok(foundation) = q_foundations.get(parent) {
-                if let Ok(dropped_card) = q_cards.get(drop.dropped) {
-                    if foundation.0 == dropped_card.suit {
-                        if let Ok(target_card) = q_cards.get(drop.target) {
-                            if !q_child.contains(drop.dropped) {
-                                if dropped_card.face.0.checked_sub(target_card.face.0) == Some(1) {
-                                    commands.entity(drop.target).add_child(drop.dropped);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        } else if let Ok(foundation) = q_foundations.get(drop.target) {
-            if let Ok(dropped_card) = q_cards.get(drop.dropped) {
-                if foundation.0 == dropped_card.suit && dropped_card.face == CardFace(1) {
-                    commands.entity(drop.target).add_child(drop.dropped);
-                }
-            }
+    for &Activate(card) in activate_event.read() {
+        if let Some(target) = rules.best_target(card) {
+            move_request_event.send(MoveRequest { card, target });
         }
     }
 }
 
-fn dropped_on_empty_pile(q_cards: &Query<&Card>, drop: &Pointer<Drop>, children: &Children, commands: &mut Commands) {
-    if let Ok(card_comp) = q_cards.get(drop.dropped) {
-        if card_comp.face == CardFace(13) {
-            if let Some(&child) = children.first() {
-                commands.entity(child).add_child(drop.dropped);
+fn move_card_event(
+    mut commands: Commands,
+    mut move_request_event: EventReader<MoveRequest>,
+    rules: Rules,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    for request in move_request_event.read() {
+        if let Some(destination) = rules.destination(request.card, request.target) {
+            commands.entity(destination).add_child(request.card);
+            if let Some(joker) = rules.resolved_joker(destination, request.card) {
+                commands.entity(destination).insert(joker);
             }
+            let to = if rules.is_foundation(destination) { Destination::Foundation } else { Destination::Pile };
+            game_event.send(GameEvent::Moved { to, run: rules.run_length(request.card) });
+        } else {
+            game_event.send(GameEvent::Rejected);
         }
     }
 }
-
-fn dropped_on_pile(q_parent: &Query<&Parent>, drop: &Pointer<Drop>, q_piles: &Query<Entity, With<Pile>>, parent: Entity, q_child: &Query<&Children>, q_cards: &Query<&Card>, commands: &mut Commands) {
-    if let Some(other_parent) = q_parent.iter_ancestors(drop.dropped).find(|&parent| q_piles.contains(parent)) {
-        if parent != other_parent {
-            move_to_pile(q_piles, parent, q_child, drop, q_cards, commands);
-        }
-    } else {
-        move_to_pile(q_piles, parent, q_child, drop, q_cards, commands);
-    }
-}
-
-fn move_to_pile(q_piles: &Query<Entity, With<Pile>>, parent: Entity, q_child: &Query<&Children>, drop: &Pointer<Drop>, q_cards: &Query<&Card>, commands: &mut Commands) {
-    if let Ok(pile) = q_piles.get(parent) {
-        if let Some(last_card) = q_child.iter_descendants(pile).last() {
-            if last_card != drop.dropped {
-                if let Ok(last_card_comp) = q_cards.get(last_card) {
-                    if let Ok(dropped_card_comp) = q_cards.get(drop.dropped) {
-                        let black_suits = [CardSuit::Clubs, CardSuit::Spades];
-                        let red_suits = [CardSuit::Hearts, CardSuit::Diamonds];
-                        let alt_suit = (
-                            black_suits.contains(&dropped_card_comp.suit) && red_suits.contains(&last_card_comp.suit)) 
-                            || (red_suits.contains(&dropped_card_comp.suit) && black_suits.contains(&last_card_comp.suit)
-                        );
-                        if alt_suit && (last_card_comp.face.0.checked_sub(dropped_card_comp.face.0) == Some(1)) {
-                            commands.entity(last_card).add_child(drop.dropped);
-                        }
-                    }
-                }
-            }
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,121 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+
+use super::foundation::{restore_foundation, Foundation};
+use super::layout::Place;
+use super::peaks::{spawn_peak_cards, PeakSlot, Peaks};
+use super::piles::{spawn_pile, Base};
+use super::reserve::{spawn_reserve, Reserve};
+use super::stock::{spawn_stock, Stock};
+use super::waste::{spawn_waste, Waste};
+use super::Card;
+
+/// Where every card is on the table, so that a position can be put back exactly as it was.
+/// Stacks are listed from the bottom card up. Anything the variant doesn't deal is `None`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Table {
+    pub stock: Option<Vec<Card>>,
+    pub waste: Option<Vec<Card>>,
+    /// Each foundation with its place on the table, from left to right.
+    pub foundations: Vec<(Foundation, Place, Vec<Card>)>,
+    /// Each tableau pile from left to right.
+    pub piles: Vec<PileCards>,
+    pub reserve: Option<Vec<Card>>,
+    pub peaks: Option<Vec<(PeakSlot, Card)>>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct PileCards {
+    pub column: f32,
+    /// How many cards from the bottom of the pile are still face down.
+    pub face_down: usize,
+    pub cards: Vec<Card>,
+}
+
+impl Table {
+    pub fn is_empty(&self) -> bool {
+        *self == Table::default()
+    }
+}
+
+/// Reads the `Table` off the entities currently on the board.
+#[derive(SystemParam)]
+pub struct Tables<'w, 's> {
+    q_stock: Query<'w, 's, Option<&'static Children>, With<Stock>>,
+    q_waste: Query<'w, 's, Entity, With<Waste>>,
+    q_foundations: Query<'w, 's, (Entity, &'static Foundation, &'static Place)>,
+    q_bases: Query<'w, 's, (&'static Place, &'static Children), With<Base>>,
+    q_reserve: Query<'w, 's, Entity, With<Reserve>>,
+    q_peaks: Query<'w, 's, &'static Children, With<Peaks>>,
+    q_slots: Query<'w, 's, &'static PeakSlot>,
+    q_children: Query<'w, 's, &'static Children>,
+    q_cards: Query<'w, 's, (&'static Card, &'static Handle<Image>)>,
+    asset_server: Res<'w, AssetServer>,
+}
+
+impl Tables<'_, '_> {
+    fn stack(&self, entity: Entity) -> Vec<Card> {
+        self.q_children.iter_descendants(entity)
+            .filter_map(|card| self.q_cards.get(card).ok().map(|(&card, _)| card))
+            .collect()
+    }
+
+    pub fn capture(&self) -> Table {
+        let back: Handle<Image> = self.asset_server.load("cards/Back Blue 1.png");
+
+        let mut foundations: Vec<_> = self.q_foundations.iter()
+            .map(|(entity, &foundation, &place)| (foundation, place, self.stack(entity)))
+            .collect();
+        foundations.sort_by(|a, b| a.1.column().total_cmp(&b.1.column()));
+
+        let mut piles: Vec<_> = self.q_bases.iter()
+            .filter_map(|(place, children)| {
+                let pile = children.first().copied()?;
+                let face_down = self.q_children.iter_descendants(pile)
+                    .filter(|&card| self.q_cards.get(card).is_ok_and(|(_, texture)| *texture == back))
+                    .count();
+                Some(PileCards { column: place.column(), face_down, cards: self.stack(pile) })
+            })
+            .collect();
+        piles.sort_by(|a, b| a.column.total_cmp(&b.column));
+
+        Table {
+            stock: self.q_stock.get_single().ok().map(|children| {
+                children.into_iter().flatten()
+                    .filter_map(|&card| self.q_cards.get(card).ok().map(|(&card, _)| card))
+                    .collect()
+            }),
+            waste: self.q_waste.get_single().ok().map(|waste| self.stack(waste)),
+            foundations,
+            piles,
+            reserve: self.q_reserve.get_single().ok().map(|reserve| self.stack(reserve)),
+            peaks: self.q_peaks.get_single().ok().map(|peaks| {
+                peaks.iter()
+                    .filter_map(|&card| Some((*self.q_slots.get(card).ok()?, *self.q_cards.get(card).ok()?.0)))
+                    .collect()
+            }),
+        }
+    }
+}
+
+/// Deals out a `Table` onto an empty board.
+pub fn spawn_table(commands: &mut Commands, table: &Table, asset_server: &Res<AssetServer>) {
+    if let Some(stock) = &table.stock {
+        spawn_stock(commands, stock.clone(), asset_server);
+    }
+    if let Some(waste) = &table.waste {
+        spawn_waste(commands, waste.clone(), asset_server);
+    }
+    for (foundation, place, cards) in &table.foundations {
+        restore_foundation(commands, *foundation, *place, cards.clone(), asset_server);
+    }
+    for pile in &table.piles {
+        spawn_pile(commands, pile.cards.clone(), pile.face_down, pile.column, asset_server);
+    }
+    if let Some(reserve) = &table.reserve {
+        spawn_reserve(commands, reserve.clone(), asset_server);
+    }
+    if let Some(peaks) = &table.peaks {
+        spawn_peak_cards(commands, peaks.clone(), asset_server);
+    }
+}
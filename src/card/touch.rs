@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+use bevy_mod_picking::prelude::*;
+
+use super::cursor::Cursor;
+use super::rules::Rules;
+use super::systems::{dragged_pointers, Activate, GameEvent, MoveRequest};
+
+/// A tap picks a card up and the next tap puts it down on whatever was tapped. Tapping the
+/// held card again sends it to its best destination, or just puts it down if it has none.
+pub fn touch_input(
+    mut click_event: EventReader<Pointer<Click>>,
+    mut drag_end_event: EventReader<Pointer<DragEnd>>,
+    mut cursor: ResMut<Cursor>,
+    rules: Rules,
+    mut activate_event: EventWriter<Activate>,
+    mut move_request_event: EventWriter<MoveRequest>,
+    mut game_event: EventWriter<GameEvent>,
+) {
+    let dragged = dragged_pointers(&mut drag_end_event);
+
+    for click in click_event.read() {
+        if !click.pointer_id.is_touch() || dragged.contains(&click.pointer_id) {
+            continue;
+        }
+        let target = click.target;
+
+        if let Some(card) = cursor.held.take() {
+            if card != target {
+                move_request_event.send(MoveRequest { card, target });
+            } else if let Some(best) = rules.best_target(card) {
+                move_request_event.send(MoveRequest { card, target: best });
+            }
+        } else if rules.variant.plays_to_waste() {
+            activate_event.send(Activate(target));
+        } else if rules.can_pick_up(target) {
+            cursor.held = Some(target);
+            game_event.send(GameEvent::PickedUp);
+        } else {
+            activate_event.send(Activate(target));
+        }
+    }
+}
@@ -0,0 +1,300 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
+
+use super::{Card, CardFace, CardSuit, JokerColour};
+
+/// The solitaire game being dealt. Cycled with F6.
+#[derive(Resource, EnumIter, Default, Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize, strum_macros::Display, strum_macros::EnumString)]
+pub enum Variant {
+    #[default]
+    Klondike,
+    Golf,
+    TriPeaks,
+    Yukon,
+    Russian,
+    Canfield,
+    FortyThieves,
+    DoubleKlondike,
+    Scorpion,
+    BakersDozen,
+}
+
+/// Optional rules layered on top of the variant.
+#[derive(Resource, Default, Copy, Clone, Debug, PartialEq)]
+pub struct HouseRules {
+    /// Shuffle both jokers into the deck as wildcards for the tableau.
+    pub jokers: bool,
+    /// Turn three cards from the stock at a time instead of one.
+    pub draw_three: bool,
+}
+
+impl HouseRules {
+    /// How many cards a click on the stock turns over onto the waste.
+    pub fn draw(self, variant: Variant) -> usize {
+        if self.draw_three && variant.allows_draw_three() { 3 } else { 1 }
+    }
+}
+
+/// How a deal is won.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum WinCondition {
+    /// Every card has been moved to the foundations.
+    Foundations,
+    /// Every card has been played off the tableau onto the waste.
+    ClearTableau,
+    /// Every card has been built into a king to ace run of one suit within the tableau.
+    Runs,
+}
+
+/// Which cards may be placed on top of each other in the tableau.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Build {
+    AlternateColour,
+    Suit,
+    AnySuit,
+}
+
+/// Which cards can be moved into an empty tableau pile.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Spaces {
+    Kings,
+    AnyCard,
+    /// Empty piles stay empty for the rest of the game.
+    Never,
+}
+
+/// Which cards can be picked up from the tableau together.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Moves {
+    /// A face-up card along with the run built on top of it.
+    Sequences,
+    /// Any face-up card along with everything on top of it, in sequence or not.
+    AnyGroup,
+    /// Only the top card of a pile.
+    SingleCards,
+}
+
+impl Variant {
+    pub fn next(self) -> Self {
+        Variant::iter().cycle().skip_while(|&variant| variant != self).nth(1).unwrap_or_default()
+    }
+
+    /// Cards are moved by dragging them onto piles and foundations.
+    pub fn drags(self) -> bool {
+        !self.plays_to_waste()
+    }
+
+    /// Cards are played from the tableau onto the waste by clicking them.
+    pub fn plays_to_waste(self) -> bool {
+        matches!(self, Variant::Golf | Variant::TriPeaks)
+    }
+
+    /// The waste can be turned back over onto the stock once the stock runs out.
+    pub fn redeals(self) -> bool {
+        matches!(self, Variant::Klondike | Variant::Canfield | Variant::DoubleKlondike)
+    }
+
+    /// A king and an ace count as neighbours, so ranks wrap around from king to ace.
+    pub fn wraps(self) -> bool {
+        matches!(self, Variant::TriPeaks | Variant::Canfield)
+    }
+
+    /// Clicking the stock deals its cards onto the tableau piles instead of the waste.
+    pub fn deals_stock_to_piles(self) -> bool {
+        matches!(self, Variant::Scorpion)
+    }
+
+    pub fn spaces(self) -> Spaces {
+        match self {
+            Variant::Canfield | Variant::FortyThieves => Spaces::AnyCard,
+            Variant::BakersDozen => Spaces::Never,
+            _ => Spaces::Kings,
+        }
+    }
+
+    pub fn moves(self) -> Moves {
+        match self {
+            Variant::Yukon | Variant::Russian | Variant::Scorpion => Moves::AnyGroup,
+            Variant::FortyThieves | Variant::BakersDozen => Moves::SingleCards,
+            _ => Moves::Sequences,
+        }
+    }
+
+    pub fn builds(self) -> Build {
+        match self {
+            Variant::Russian | Variant::FortyThieves | Variant::Scorpion => Build::Suit,
+            Variant::BakersDozen => Build::AnySuit,
+            _ => Build::AlternateColour,
+        }
+    }
+
+    /// How many cards wide the table has to be for the widest row of the deal.
+    pub fn columns(self) -> usize {
+        match self {
+            Variant::FortyThieves | Variant::DoubleKlondike | Variant::TriPeaks => 10,
+            Variant::BakersDozen => 13,
+            _ => 7,
+        }
+    }
+
+    /// The column of the tableau row that pile `index` goes in, counting piles from 0.
+    pub fn pile_column(self, index: usize) -> f32 {
+        match self {
+            // Nine piles under ten columns of stock, waste and foundations, so they sit half a column in.
+            Variant::DoubleKlondike => index as f32 + 0.5,
+            // The reserve takes the first column.
+            Variant::Canfield => index as f32 + 1.0,
+            _ => index as f32,
+        }
+    }
+
+    /// The column of the top row the first foundation goes in. They go at the right-hand end.
+    pub fn foundation_column(self) -> usize {
+        self.columns() - 4 * self.decks()
+    }
+
+    pub fn decks(self) -> usize {
+        match self {
+            Variant::FortyThieves | Variant::DoubleKlondike => 2,
+            _ => 1,
+        }
+    }
+
+    /// Variants with a stock and foundations, which the jokers can be shuffled into.
+    pub fn allows_jokers(self) -> bool {
+        matches!(self, Variant::Klondike | Variant::Canfield | Variant::FortyThieves | Variant::DoubleKlondike)
+    }
+
+    pub fn allows_draw_three(self) -> bool {
+        matches!(self, Variant::Klondike)
+    }
+
+    /// Whether `upper` may be placed on `lower` in the tableau, with jokers standing in for whatever fits.
+    pub fn can_build(self, lower: &Card, upper: &Card) -> bool {
+        let (Some(upper_suit), Some(upper_face)) = (upper.suit(), upper.face()) else {
+            let &Card::Joker { colour, .. } = upper else {
+                return false;
+            };
+            let Some(lower_face) = lower.face() else {
+                return false;
+            };
+            let face = match lower_face.0 {
+                1 if self.wraps() => CardFace(13),
+                1 => return false,
+                face => CardFace(face - 1),
+            };
+            return CardSuit::iter().any(|suit| self.fits_colour(colour, suit) && self.can_build(lower, &Card::Suited { suit, face }));
+        };
+        let (Some(lower_suit), Some(lower_face)) = (lower.suit(), lower.face()) else {
+            let &Card::Joker { colour, .. } = lower else {
+                return false;
+            };
+            return self.stand_in(None, colour, upper).is_some();
+        };
+        let follows_suit = match self.builds() {
+            Build::AlternateColour => lower_suit.is_red() != upper_suit.is_red(),
+            Build::Suit => lower_suit == upper_suit,
+            Build::AnySuit => true,
+        };
+        let follows_rank = lower_face.0.checked_sub(upper_face.0) == Some(1)
+            || (self.wraps() && lower_face.0 == 1 && upper_face.0 == 13);
+        follows_suit && follows_rank
+    }
+
+    /// The card a joker of `colour` stands in for under `upper`, and on `below` if it's on a card.
+    pub fn stand_in(self, below: Option<&Card>, colour: JokerColour, upper: &Card) -> Option<(CardSuit, CardFace)> {
+        let face = match upper.face()?.0 {
+            13 if self.wraps() => CardFace(1),
+            13 => return None,
+            face => CardFace(face + 1),
+        };
+        CardSuit::iter()
+            .filter(|&suit| self.fits_colour(colour, suit))
+            .find(|&suit| {
+                let stand_in = Card::Suited { suit, face };
+                self.can_build(&stand_in, upper) && below.is_none_or(|below| self.can_build(below, &stand_in))
+            })
+            .map(|suit| (suit, face))
+    }
+
+    fn fits_colour(self, colour: JokerColour, suit: CardSuit) -> bool {
+        self.builds() == Build::AnySuit || suit.is_red() == (colour == JokerColour::Red)
+    }
+
+    pub fn win_condition(self) -> WinCondition {
+        match self {
+            Variant::Golf | Variant::TriPeaks => WinCondition::ClearTableau,
+            Variant::Scorpion => WinCondition::Runs,
+            _ => WinCondition::Foundations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn card(suit: CardSuit, face: u8) -> Card {
+        Card::Suited { suit, face: CardFace(face) }
+    }
+
+    fn joker(colour: JokerColour) -> Card {
+        Card::Joker { colour, stands_for: None }
+    }
+
+    #[test]
+    fn builds_alternate_colours_down() {
+        assert!(Variant::Klondike.can_build(&card(CardSuit::Hearts, 7), &card(CardSuit::Spades, 6)));
+        assert!(!Variant::Klondike.can_build(&card(CardSuit::Hearts, 7), &card(CardSuit::Diamonds, 6)));
+        assert!(!Variant::Klondike.can_build(&card(CardSuit::Hearts, 7), &card(CardSuit::Spades, 5)));
+    }
+
+    #[test]
+    fn joker_goes_on_a_card_of_its_colour_only() {
+        assert!(Variant::Klondike.can_build(&card(CardSuit::Hearts, 7), &joker(JokerColour::Black)));
+        assert!(!Variant::Klondike.can_build(&card(CardSuit::Hearts, 7), &joker(JokerColour::Red)));
+        assert!(!Variant::Klondike.can_build(&card(CardSuit::Hearts, 1), &joker(JokerColour::Black)));
+    }
+
+    #[test]
+    fn joker_takes_a_card_it_can_stand_in_under() {
+        assert!(Variant::Klondike.can_build(&joker(JokerColour::Black), &card(CardSuit::Hearts, 5)));
+        assert!(!Variant::Klondike.can_build(&joker(JokerColour::Black), &card(CardSuit::Spades, 5)));
+        assert!(!Variant::Klondike.can_build(&joker(JokerColour::Black), &card(CardSuit::Spades, 13)));
+        assert!(!Variant::Klondike.can_build(&joker(JokerColour::Black), &joker(JokerColour::Red)));
+    }
+
+    #[test]
+    fn stand_in_fits_the_card_below() {
+        let below = card(CardSuit::Hearts, 7);
+        assert_eq!(
+            Variant::Klondike.stand_in(Some(&below), JokerColour::Black, &card(CardSuit::Hearts, 5)),
+            Some((CardSuit::Clubs, CardFace(6)))
+        );
+        assert_eq!(Variant::Klondike.stand_in(Some(&below), JokerColour::Black, &card(CardSuit::Spades, 2)), None);
+        assert_eq!(Variant::Klondike.stand_in(Some(&below), JokerColour::Black, &card(CardSuit::Hearts, 4)), None);
+    }
+
+    #[test]
+    fn stand_in_follows_suit() {
+        let below = card(CardSuit::Spades, 9);
+        assert_eq!(
+            Variant::Russian.stand_in(None, JokerColour::Black, &card(CardSuit::Clubs, 7)),
+            Some((CardSuit::Clubs, CardFace(8)))
+        );
+        assert_eq!(Variant::Scorpion.stand_in(Some(&below), JokerColour::Black, &card(CardSuit::Clubs, 7)), None);
+        assert_eq!(Variant::Scorpion.stand_in(None, JokerColour::Red, &card(CardSuit::Clubs, 7)), None);
+    }
+
+    #[test]
+    fn stand_in_wraps_kings_to_aces() {
+        assert_eq!(Variant::Klondike.stand_in(None, JokerColour::Red, &card(CardSuit::Spades, 13)), None);
+        assert_eq!(
+            Variant::Canfield.stand_in(None, JokerColour::Red, &card(CardSuit::Spades, 13)),
+            Some((CardSuit::Hearts, CardFace(1)))
+        );
+        assert!(Variant::Canfield.can_build(&card(CardSuit::Clubs, 1), &joker(JokerColour::Red)));
+    }
+}
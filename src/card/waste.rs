@@ -1,15 +1,17 @@
 use bevy::prelude::*;
 use bevy_mod_picking::prelude::*;
 
-use super::{Board, Card};
+use super::layout::Place;
+use super::{Board, Card, CardBundle};
 
 #[derive(Component)]
 pub struct Waste;
 
-pub fn spawn_waste(commands: &mut Commands, asset_server: &Res<AssetServer>) {
-    commands.spawn((
+/// Spawns the waste with `cards` already on it, from the bottom up.
+pub fn spawn_waste(commands: &mut Commands, cards: impl IntoIterator<Item = Card>, asset_server: &Res<AssetServer>) {
+    let waste = commands.spawn((
         SpriteBundle {
-            transform: Transform::from_xyz(-350.0, 275.0, -100.0),
+            transform: Transform::from_xyz(0.0, 0.0, -100.0),
             texture: asset_server.load("cards/Back Blue 1.png"),
             sprite: Sprite {
                 color: Color::Rgba { red: 0.5, green: 0.5, blue: 0.5, alpha: 0.25 },
@@ -18,23 +20,39 @@ pub fn spawn_waste(commands: &mut Commands, asset_server: &Res<AssetServer>) {
             ..default()
         },
         Waste,
+        Place::Top(1.0),
         Board,
         PickableBundle {
             pickable: Pickable::IGNORE,
             ..default()
         },
-        On::<Pointer<Drag>>::target_component_mut::<Transform>(|drag, transform| {
-            transform.translation.x += drag.delta.x;
-            transform.translation.y -= drag.delta.y;
-            transform.translation.z = 1000.0;
-        })
     ));
+
+    let mut parent = waste.id();
+    for card in cards {
+        let child = commands.spawn((
+            CardBundle {
+                card,
+                sprite: SpriteBundle {
+                    transform: Transform::from_xyz(0.0, 0.0, 1.0),
+                    texture: asset_server.load(card.texture()),
+                    ..default()
+                },
+                pickable_bundle: PickableBundle::default()
+            },
+        )).id();
+        commands.entity(parent).add_child(child);
+        parent = child;
+    }
 }
 
+/// Squares the waste up, with the top `fanned` cards spread out by `fan`.
 pub fn format_waste(
     q_waste: &Query<&Children, With<Waste>>,
     q_children: &Query<&Children, With<Card>>,
-    transform_query: &mut Query<&mut Transform, With<Card>>
+    transform_query: &mut Query<&mut Transform, With<Card>>,
+    fanned: usize,
+    fan: f32,
 ) {
     for children in q_waste {
         for &child in children {
@@ -43,11 +61,13 @@ pub fn format_waste(
                 transform.translation.y = 0.0;
                 transform.translation.z = 1.0;
             }
-            
-            if let Some(top_card) = q_children.iter_descendants(child).last() {
-                for child in q_children.iter_descendants(child) {
+
+            let cards: Vec<Entity> = q_children.iter_descendants(child).collect();
+            let spread_from = (cards.len() + 1).saturating_sub(fanned);
+            if let Some(&top_card) = cards.last() {
+                for (i, &child) in cards.iter().enumerate() {
                     if let Ok(mut transform) = transform_query.get_mut(child) {
-                        transform.translation.x = 0.0;
+                        transform.translation.x = if i >= spread_from { fan } else { 0.0 };
                         transform.translation.y = 0.0;
                         if child == top_card {
                             transform.translation.z = 1.0;
@@ -57,4 +77,36 @@ pub fn format_waste(
             }
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::card::{CardFace, CardSuit};
+
+    #[test]
+    fn spreads_the_top_three_cards_a_fan_apart() {
+        let mut world = World::new();
+        let mut parent = world.spawn((Waste, Transform::default())).id();
+        let mut cards = Vec::new();
+        for face in 1..6 {
+            let card = world.spawn((Card::Suited { suit: CardSuit::Clubs, face: CardFace(face) }, Transform::default())).id();
+            world.entity_mut(parent).add_child(card);
+            cards.push(card);
+            parent = card;
+        }
+
+        world.run_system_once(|q_waste: Query<&Children, With<Waste>>, q_children: Query<&Children, With<Card>>, mut transform_query: Query<&mut Transform, With<Card>>| {
+            format_waste(&q_waste, &q_children, &mut transform_query, 3, 10.0);
+        });
+
+        let mut x = 0.0;
+        let positions: Vec<f32> = cards.iter().map(|&card| {
+            x += world.get::<Transform>(card).unwrap().translation.x;
+            x
+        }).collect();
+        assert_eq!(positions, [0.0, 0.0, 0.0, 10.0, 20.0]);
+    }
+}
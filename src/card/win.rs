@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+
+use super::foundation::Foundation;
+use super::peaks::Peaks;
+use super::piles::Pile;
+use super::variant::{Variant, WinCondition};
+use super::{Card, GameState, NewGame};
+
+/// Set once the current deal has been won so the win is only announced once.
+#[derive(Resource, Default)]
+pub struct Won(pub bool);
+
+#[derive(Event)]
+pub struct GameWon;
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub fn check_win(
+    variant: Res<Variant>,
+    mut won: ResMut<Won>,
+    mut game_won_event: EventWriter<GameWon>,
+    q_cards: Query<(Entity, &Card)>,
+    q_parent: Query<&Parent>,
+    q_foundations: Query<(), With<Foundation>>,
+    q_tableau: Query<(), Or<(With<Pile>, With<Peaks>)>>,
+    q_piles: Query<Entity, With<Pile>>,
+    q_children: Query<&Children>,
+) {
+    if won.0 || q_cards.is_empty() {
+        return;
+    }
+
+    let finished = match variant.win_condition() {
+        // Jokers can't go to the foundations, so they are left wherever they are.
+        WinCondition::Foundations => q_cards.iter()
+            .filter(|(_, card)| !card.is_joker())
+            .all(|(card, _)| q_parent.iter_ancestors(card).any(|parent| q_foundations.contains(parent))),
+        WinCondition::ClearTableau => !q_cards.iter()
+            .any(|(card, _)| q_parent.iter_ancestors(card).any(|parent| q_tableau.contains(parent))),
+        WinCondition::Runs => q_cards.iter()
+            .all(|(card, _)| q_parent.iter_ancestors(card).any(|parent| q_tableau.contains(parent)))
+            && q_piles.iter().all(|pile| {
+                let run: Vec<&Card> = q_children.iter_descendants(pile)
+                    .filter_map(|card| q_cards.get(card).ok().map(|(_, card)| card))
+                    .collect();
+                run.is_empty() || (run.len() == 13 && run.windows(2).all(|pair| variant.can_build(pair[0], pair[1])))
+            }),
+    };
+
+    if finished {
+        won.0 = true;
+        game_won_event.send(GameWon);
+    }
+}
+
+pub fn announce_win(
+    mut game_won_event: EventReader<GameWon>,
+    variant: Res<Variant>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for _ in game_won_event.read() {
+        info!("{} won!", *variant);
+        // A replay carries on to the end of the recording.
+        if *state.get() == GameState::Playing {
+            next_state.set(GameState::Won);
+        }
+    }
+}
+
+pub fn reset_won(mut new_game_event: EventReader<NewGame>, mut won: ResMut<Won>) {
+    if new_game_event.read().last().is_some() {
+        won.0 = false;
+    }
+}